@@ -15,6 +15,12 @@ impl App {
     }
 }
 
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let window = event_loop
@@ -38,6 +44,9 @@ impl ApplicationHandler for App {
                 WindowEvent::CloseRequested => {
                     event_loop.exit();
                 }
+                WindowEvent::Resized(size) => {
+                    self.state.as_mut().unwrap().resize(size);
+                }
                 WindowEvent::RedrawRequested => {
                     let now = time::Instant::now();
                     let delta_time = now.duration_since(self.last_frame_time);
@@ -5,11 +5,12 @@ use wgpu::util::DeviceExt;
 use winit::{dpi::PhysicalSize, window::Window};
 
 use crate::{
-    fluid_simulation::FluidSimulation,
+    fluid_simulation::{FluidSimulation, Kernel, SimulationParams, TimeStepping},
     pipelines::{
         create_field_render_pipeline, create_filed_compute_pipeline,
         create_particle_render_pipeline,
     },
+    view::ViewUniform,
 };
 
 // TODO: remove
@@ -17,7 +18,13 @@ const ROWS: u32 = 32;
 const COLS: u32 = 32;
 const TOP: f32 = -0.5;
 const LEFT: f32 = -0.5;
-const WINDOW_SIZE: u32 = 800;
+// Width and height of the simulated tank in meters
+const DOMAIN_SIZE: (f32, f32) = (2.0, 2.0);
+const TIME_SCALE: f32 = 0.02;
+const DENSITY_KERNEL_SAMPLES: usize = 256;
+const PARTICLE_CAPACITY: usize = 4096;
+// Colors particles by effective viscosity up to this value instead of by phase
+const VISCOSITY_COLOR_RANGE: Option<f32> = None;
 
 pub struct State {
     surface: wgpu::Surface<'static>,
@@ -26,16 +33,28 @@ pub struct State {
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
     pipeline: wgpu::RenderPipeline,
+    render_bind_group: wgpu::BindGroup,
+    view_buffer: wgpu::Buffer,
 
     simulation: FluidSimulation,
+    particle_capacity: usize,
     particle_position_buffer: wgpu::Buffer,
     particle_density_buffer: wgpu::Buffer,
+    particle_color_buffer: wgpu::Buffer,
+    particle_count_buffer: wgpu::Buffer,
+    density_kernel_buffer: wgpu::Buffer,
     field_texture: wgpu::Texture,
+    field_texture_view: wgpu::TextureView,
+    field_texture_sampler: wgpu::Sampler,
     field_compute_pipeline: wgpu::ComputePipeline,
+    field_compute_bind_group_layout: wgpu::BindGroupLayout,
     field_compute_bind_group: wgpu::BindGroup,
 
     field_render_pipeline: wgpu::RenderPipeline,
+    field_render_bind_group_layout: wgpu::BindGroupLayout,
     field_render_bind_group: wgpu::BindGroup,
+    // Params the view and density kernel were last built from
+    view_params: SimulationParams,
 
     window: Arc<Window>,
 }
@@ -50,27 +69,54 @@ impl State {
         let (device, queue) = Self::create_device(&adapter);
         let surface_caps = surface.get_capabilities(&adapter);
         let config = Self::create_surface_config(size, surface_caps);
-        let pipeline = create_particle_render_pipeline(&device, &config);
+        let (pipeline, render_bind_group_layout) =
+            create_particle_render_pipeline(&device, &config);
         surface.configure(&device, &config);
 
-        let smoothing_radius = 0.04;
-        let bound_damping = -0.5;
-        let mass = 0.001;
-        let viscosity = 0.001;
-
-        let simulation = FluidSimulation::with_grid_initialization(
-            smoothing_radius,
-            bound_damping,
-            mass,
-            viscosity,
-            ROWS,
-            COLS,
-            TOP,
-            LEFT,
-        );
-        let (particle_position_buffer, particle_density_buffer) =
-            Self::create_particle_buffers(&device, &simulation);
-        let field_texture = Self::create_field_texture(&device, WINDOW_SIZE, WINDOW_SIZE);
+        let params = SimulationParams::builder()
+            .smoothing_radius(0.04)
+            .bound_damping(-0.5)
+            .mass(0.001)
+            .viscosity(0.001)
+            .domain_size(nalgebra::Vector2::new(DOMAIN_SIZE.0, DOMAIN_SIZE.1))
+            .time_stepping(TimeStepping {
+                time_scale: TIME_SCALE,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        let simulation = FluidSimulation::with_grid_initialization(params, ROWS, COLS, TOP, LEFT)
+            .with_capacity(PARTICLE_CAPACITY);
+        let particle_capacity = simulation.capacity();
+        let (particle_position_buffer, particle_density_buffer, particle_color_buffer) =
+            Self::create_particle_buffers(&device, particle_capacity);
+        let particle_count_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle count buffer"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let density_kernel_buffer = Self::create_density_kernel_buffer(&device, &simulation);
+
+        let view = ViewUniform::fit(&simulation.params().domain(), size.width, size.height);
+        let view_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("View buffer"),
+            contents: bytemuck::bytes_of(&view),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Render bind group"),
+            layout: &render_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: view_buffer.as_entire_binding(),
+            }],
+        });
+
+        // One texel per pixel of the domain on screen
+        let (field_width, field_height) = view.domain_pixels(size.width, size.height);
+        let field_texture = Self::create_field_texture(&device, field_width, field_height);
 
         let field_texture_view = field_texture.create_view(&wgpu::TextureViewDescriptor::default());
         let field_texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
@@ -85,42 +131,28 @@ impl State {
 
         let (field_compute_pipeline, field_compute_bind_group_layout) =
             create_filed_compute_pipeline(&device);
-        let field_compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Filed compute bind group"),
-            layout: &field_compute_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: particle_position_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: particle_density_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&field_texture_view),
-                },
-            ],
-        });
+        let field_compute_bind_group = Self::create_field_compute_bind_group(
+            &device,
+            &field_compute_bind_group_layout,
+            &particle_position_buffer,
+            &particle_density_buffer,
+            &field_texture_view,
+            &density_kernel_buffer,
+            &particle_count_buffer,
+            &view_buffer,
+        );
 
         let (field_render_pipeline, field_render_bind_group_layout) =
             create_field_render_pipeline(&device, &config);
 
-        let field_render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Filed render bind group"),
-            layout: &field_render_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&field_texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&field_texture_sampler),
-                },
-            ],
-        });
+        let field_render_bind_group = Self::create_field_render_bind_group(
+            &device,
+            &field_render_bind_group_layout,
+            &field_texture_view,
+            &field_texture_sampler,
+            &view_buffer,
+        );
+        let view_params = simulation.params().clone();
 
         Self {
             surface,
@@ -129,45 +161,237 @@ impl State {
             config,
             size,
             pipeline,
+            render_bind_group,
+            view_buffer,
             simulation,
+            particle_capacity,
             particle_position_buffer,
             particle_density_buffer,
+            particle_color_buffer,
+            particle_count_buffer,
+            density_kernel_buffer,
             field_texture,
+            field_texture_view,
+            field_texture_sampler,
             field_compute_pipeline,
+            field_compute_bind_group_layout,
             field_compute_bind_group,
+            field_render_bind_group_layout,
             field_render_bind_group,
             field_render_pipeline,
+            view_params,
             window: window_arc,
         }
     }
 
+    /// Position, density and color buffers with room for `capacity` particles.
     fn create_particle_buffers(
         device: &wgpu::Device,
-        simulation: &FluidSimulation,
-    ) -> (wgpu::Buffer, wgpu::Buffer) {
-        let position_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        capacity: usize,
+    ) -> (wgpu::Buffer, wgpu::Buffer, wgpu::Buffer) {
+        let capacity = capacity.max(1) as wgpu::BufferAddress;
+
+        let position_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Particle position buffer"),
-            contents: simulation.positions_data(),
+            size: capacity * std::mem::size_of::<nalgebra::Vector2<f32>>() as wgpu::BufferAddress,
             usage: wgpu::BufferUsages::VERTEX
                 | wgpu::BufferUsages::STORAGE
                 | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
 
-        let density_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        let density_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Particle density buffer"),
-            contents: simulation.density_data(),
+            size: capacity * std::mem::size_of::<f32>() as wgpu::BufferAddress,
             usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let color_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle color buffer"),
+            size: capacity * std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
 
-        (position_buffer, density_buffer)
+        (position_buffer, density_buffer, color_buffer)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_field_compute_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        particle_position_buffer: &wgpu::Buffer,
+        particle_density_buffer: &wgpu::Buffer,
+        field_texture_view: &wgpu::TextureView,
+        density_kernel_buffer: &wgpu::Buffer,
+        particle_count_buffer: &wgpu::Buffer,
+        view_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Filed compute bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: particle_position_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particle_density_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(field_texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: density_kernel_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: particle_count_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: view_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    fn create_field_render_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        field_texture_view: &wgpu::TextureView,
+        field_texture_sampler: &wgpu::Sampler,
+        view_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Filed render bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(field_texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(field_texture_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: view_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Refits the view to the window and rebuilds the field texture and
+    /// density kernel, which depend on it and on the simulation params.
+    fn update_view(&mut self) {
+        let view = ViewUniform::fit(
+            &self.simulation.params().domain(),
+            self.size.width,
+            self.size.height,
+        );
+        self.queue
+            .write_buffer(&self.view_buffer, 0, bytemuck::bytes_of(&view));
+
+        let (field_width, field_height) = view.domain_pixels(self.size.width, self.size.height);
+        self.field_texture = Self::create_field_texture(&self.device, field_width, field_height);
+        self.field_texture_view = self
+            .field_texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.density_kernel_buffer =
+            Self::create_density_kernel_buffer(&self.device, &self.simulation);
+        self.view_params = self.simulation.params().clone();
+
+        self.field_compute_bind_group = Self::create_field_compute_bind_group(
+            &self.device,
+            &self.field_compute_bind_group_layout,
+            &self.particle_position_buffer,
+            &self.particle_density_buffer,
+            &self.field_texture_view,
+            &self.density_kernel_buffer,
+            &self.particle_count_buffer,
+            &self.view_buffer,
+        );
+        self.field_render_bind_group = Self::create_field_render_bind_group(
+            &self.device,
+            &self.field_render_bind_group_layout,
+            &self.field_texture_view,
+            &self.field_texture_sampler,
+            &self.view_buffer,
+        );
+    }
+
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        // Minimized windows have no surface to draw to
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        self.size = size;
+        self.config.width = size.width;
+        self.config.height = size.height;
+        self.surface.configure(&self.device, &self.config);
+        self.update_view();
+    }
+
+    /// Reallocates the particle buffers when the simulation capacity changed.
+    fn resize_particle_buffers(&mut self) {
+        if self.simulation.capacity() == self.particle_capacity {
+            return;
+        }
+
+        self.particle_capacity = self.simulation.capacity();
+        (
+            self.particle_position_buffer,
+            self.particle_density_buffer,
+            self.particle_color_buffer,
+        ) = Self::create_particle_buffers(&self.device, self.particle_capacity);
+        self.field_compute_bind_group = Self::create_field_compute_bind_group(
+            &self.device,
+            &self.field_compute_bind_group_layout,
+            &self.particle_position_buffer,
+            &self.particle_density_buffer,
+            &self.field_texture_view,
+            &self.density_kernel_buffer,
+            &self.particle_count_buffer,
+            &self.view_buffer,
+        );
+    }
+
+    /// Smoothing radius followed by `mass * W(r)` of the density kernel, sampled for the field shader.
+    fn create_density_kernel_buffer(
+        device: &wgpu::Device,
+        simulation: &FluidSimulation,
+    ) -> wgpu::Buffer {
+        let params = simulation.params();
+        let smoothing_radius = params.smoothing_radius();
+        let kernel = params.kernels().density;
+
+        let mut data = Vec::with_capacity(DENSITY_KERNEL_SAMPLES + 1);
+        data.push(smoothing_radius);
+        data.extend((0..DENSITY_KERNEL_SAMPLES).map(|i| {
+            let r = i as f32 / (DENSITY_KERNEL_SAMPLES - 1) as f32 * smoothing_radius;
+            params.mass() * kernel.value(r, smoothing_radius)
+        }));
+
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Density kernel buffer"),
+            contents: bytemuck::cast_slice(&data),
+            usage: wgpu::BufferUsages::STORAGE,
+        })
     }
 
     fn create_field_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
         device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Field texture"),
             size: wgpu::Extent3d {
-                width: width,
-                height: height,
+                width,
+                height,
                 depth_or_array_layers: 1,
             },
             mip_level_count: 1,
@@ -221,7 +445,7 @@ impl State {
         instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
+                compatible_surface: Some(surface),
                 force_fallback_adapter: false,
             })
             .block_on()
@@ -240,6 +464,34 @@ impl State {
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.resize_particle_buffers();
+        if self.simulation.params() != &self.view_params {
+            self.update_view();
+        }
+        self.queue.write_buffer(
+            &self.particle_position_buffer,
+            0,
+            self.simulation.positions_data(),
+        );
+        self.queue.write_buffer(
+            &self.particle_density_buffer,
+            0,
+            self.simulation.density_data(),
+        );
+        self.queue.write_buffer(
+            &self.particle_color_buffer,
+            0,
+            bytemuck::cast_slice(&match VISCOSITY_COLOR_RANGE {
+                Some(max_viscosity) => self.simulation.viscosity_colors(max_viscosity),
+                None => self.simulation.particle_colors(),
+            }),
+        );
+        self.queue.write_buffer(
+            &self.particle_count_buffer,
+            0,
+            bytemuck::bytes_of(&self.simulation.num_particles()),
+        );
+
         let output = self.surface.get_current_texture()?;
         let view = output
             .texture
@@ -259,7 +511,11 @@ impl State {
 
             compute_pass.set_pipeline(&self.field_compute_pipeline);
             compute_pass.set_bind_group(0, &self.field_compute_bind_group, &[]);
-            compute_pass.dispatch_workgroups((WINDOW_SIZE + 15) / 16, (WINDOW_SIZE + 15) / 16, 1);
+            compute_pass.dispatch_workgroups(
+                self.field_texture.width().div_ceil(16),
+                self.field_texture.height().div_ceil(16),
+                1,
+            );
         }
 
         {
@@ -300,20 +556,12 @@ impl State {
             });
 
             render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.render_bind_group, &[]);
             render_pass.set_vertex_buffer(0, self.particle_position_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, self.particle_color_buffer.slice(..));
             render_pass.draw(0..4, 0..self.simulation.num_particles());
         }
 
-        self.queue.write_buffer(
-            &self.particle_position_buffer,
-            0,
-            self.simulation.positions_data(),
-        );
-        self.queue.write_buffer(
-            &self.particle_density_buffer,
-            0,
-            self.simulation.density_data(),
-        );
         self.queue.submit(std::iter::once(encoder.finish()));
         output.present();
 
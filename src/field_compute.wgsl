@@ -1,39 +1,54 @@
 @group(0) @binding(0) var<storage, read> particle_positions: array<vec2<f32>>; 
 @group(0) @binding(1) var<storage, read> particle_densities: array<f32>; 
 @group(0) @binding(2) var density_field: texture_storage_2d<r32float, write>; // Output texture data
+@group(0) @binding(3) var<storage, read> density_kernel: DensityKernel;
+@group(0) @binding(4) var<uniform> particle_count: u32; // Particle buffers are allocated for the simulation's capacity
+@group(0) @binding(5) var<uniform> view: View;
+
+// World to clip space, clip = world * scale + offset
+struct View {
+    scale: vec2<f32>,
+    offset: vec2<f32>,
+    half_extents: vec2<f32>,
+}
+
+// mass * W(r) of the simulation's density kernel sampled uniformly on [0, smoothing_radius]
+struct DensityKernel {
+    smoothing_radius: f32,
+    samples: array<f32>,
+}
 
-const PI: f32 = 3.1415927;
-const H: f32 = 0.04;
-const HSQ: f32 = H * H;
-const POLY6: f32 = 4.0 / (PI * H * H * H * H * H * H * H * H);
-const MASS: f32 = 0.001;
+fn kernel_contribution(dist: f32) -> f32 {
+    let last = arrayLength(&density_kernel.samples) - 1u;
+    let x = dist / density_kernel.smoothing_radius * f32(last);
+    let i = min(u32(x), last - 1u);
+
+    return mix(density_kernel.samples[i], density_kernel.samples[i + 1u], x - f32(i));
+}
 
 @compute @workgroup_size(16, 16)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
-    let resolution = 800u; // Resolution of the density field
-    let grid_size = f32(resolution);
+    let resolution = textureDimensions(density_field); // The density field covers the domain
+    let grid_size = vec2<f32>(resolution);
 
-    if (global_id.x >= resolution || global_id.y >= resolution) {
+    if (global_id.x >= resolution.x || global_id.y >= resolution.y) {
         return;
     }
 
-    // Compute normalized coordinates for the grid cell
+    // Compute world coordinates for the grid cell
     let grid_pos = vec2<f32>(
-        f32(global_id.x) / grid_size * 2.0 - 1.0,
-        f32(global_id.y) / grid_size * 2.0 - 1.0,
-    );
+        f32(global_id.x) / grid_size.x * 2.0 - 1.0,
+        f32(global_id.y) / grid_size.y * 2.0 - 1.0,
+    ) * view.half_extents;
 
     var density: f32 = 0.0;
 
     // Sum contributions from all particles
-    for (var i = 0u; i < arrayLength(&particle_positions); i++) {
+    for (var i = 0u; i < particle_count; i++) {
         let dist = length(grid_pos - particle_positions[i]);
-        let dist_sq = dist * dist;
 
-        if (dist < H) {
-            // Example: Poly6 kernel contribution
-            let contribution = MASS * POLY6 * (HSQ - dist_sq) * (HSQ - dist_sq) * (HSQ - dist_sq);
-            density += contribution;
+        if (dist < density_kernel.smoothing_radius) {
+            density += kernel_contribution(dist);
         }
     }
 
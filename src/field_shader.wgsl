@@ -1,3 +1,13 @@
+// World to clip space, clip = world * scale + offset
+struct View {
+    scale: vec2<f32>,
+    offset: vec2<f32>,
+    half_extents: vec2<f32>,
+};
+
+@group(0) @binding(2)
+var<uniform> view: View;
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) texture_coords: vec2<f32>,
@@ -14,9 +24,13 @@ fn vs_main(
         vec2f( 1.0,  1.0),
     );
 
+    // The field texture covers the domain
+    let corner = quad_vertices[in_vertex_index];
+    let world_pos = corner * view.half_extents;
+
     var out: VertexOutput;
-    out.clip_position = vec4f(quad_vertices[in_vertex_index], 0.0, 1.0);
-    out.texture_coords = vec2f(out.clip_position.x / 2.0 + 0.5, out.clip_position.y / 2.0 + 0.5);
+    out.clip_position = vec4f(world_pos * view.scale + view.offset, 0.0, 1.0);
+    out.texture_coords = vec2f(corner.x / 2.0 + 0.5, corner.y / 2.0 + 0.5);
     return out;
 }
 
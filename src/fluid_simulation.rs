@@ -1,30 +1,94 @@
-use std::f32::consts::PI;
+mod artificial_viscosity;
+mod boundary;
+mod dfsph;
+mod domain;
+mod emitters;
+mod equation_of_state;
+mod heat;
+mod iisph;
+mod integrators;
+mod kernels;
+mod motion;
+mod neighbor_search;
+mod obstacles;
+mod params;
+mod particles;
+mod pbf;
+mod pcisph;
+mod phases;
+mod rheology;
+mod rigid_body;
+mod solver;
+mod sph;
+mod surface_tension;
+mod time_stepping;
+mod viscoelastic;
 
-use nalgebra::{Normed, Vector2};
+use nalgebra::Vector2;
 
-const DT: f32 = 0.0001;
-const REST_DENS: f32 = 1.0;
-const GAS_CONST: f32 = 10.0;
-pub const G: Vector2<f32> = Vector2::new(0.0, -1.0);
+pub use artificial_viscosity::ArtificialViscosity;
+pub use boundary::BoundaryParticles;
+pub use dfsph::Dfsph;
+pub use domain::{BoundaryMode, Domain, Image};
+pub use emitters::{Emitter, EmitterShape, Sink};
+pub use equation_of_state::EquationOfState;
+pub use heat::Heat;
+pub use iisph::Iisph;
+pub use integrators::{
+    AccelerationFn, Integrator, Leapfrog, RungeKutta4, SymplecticEuler, VelocityVerlet,
+};
+pub use kernels::{
+    CubicSpline, Kernel, Kernels, Poly6, SmoothingKernel, Spiky, Viscosity, WendlandC2, WendlandC4,
+};
+pub use motion::{KinematicState, Motion};
+pub use neighbor_search::{NeighborList, NeighborSearch};
+pub use obstacles::{Capsule, Circle, Obstacle, Polygon, SignedDistance};
+pub use params::{ParamsError, SimulationParams, SimulationParamsBuilder};
+pub use particles::Particles;
+pub use pbf::Pbf;
+pub use pcisph::Pcisph;
+pub use phases::Phase;
+pub use rheology::ViscosityModel;
+pub use rigid_body::{RigidBody, RigidShape};
+pub use solver::{Solver, SolverContext, SolverStats, Wcsph};
+pub use surface_tension::SurfaceTension;
+pub use time_stepping::{AdaptiveTimestep, TimeStepping};
+pub use viscoelastic::Viscoelastic;
+
+use time_stepping::Accumulator;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StepStats {
+    pub dt: f32,
+    pub iterations: u32,
+    pub density_error: f32,
+}
 
 pub struct FluidSimulation {
-    smoothing_radius: f32,
-    bound_damping: f32,
-    mass: f32,
-    viscosity: f32,
-
-    positions: Vec<Vector2<f32>>,
-    velocities: Vec<Vector2<f32>>,
-    densities: Vec<f32>,
-    pressures: Vec<f32>,
+    params: SimulationParams,
+
+    particles: Particles,
+    neighbors: NeighborList,
+    solver: Box<dyn Solver>,
+    boundary: Option<BoundaryParticles>,
+    obstacles: Vec<Obstacle>,
+    rigid_bodies: Vec<RigidBody>,
+    emitters: Vec<Emitter>,
+    sinks: Vec<Sink>,
+    capacity: usize,
+    container_motion: Motion,
+
+    xsph_corrections: Vec<Vector2<f32>>,
+    heat_rates: Vec<f32>,
+
+    accumulator: Accumulator,
+    time: f32,
+    last_step: StepStats,
 }
 
 impl FluidSimulation {
     pub fn with_grid_initialization(
-        smoothing_radius: f32,
-        bound_damping: f32,
-        mass: f32,
-        viscosity: f32,
+        params: SimulationParams,
         rows: u32,
         cols: u32,
         top: f32,
@@ -32,8 +96,6 @@ impl FluidSimulation {
     ) -> Self {
         let mut positions = Vec::with_capacity((rows * cols) as usize);
         let mut velocities = Vec::with_capacity((rows * cols) as usize);
-        let mut densities = vec![0.0; (rows * cols) as usize];
-        let mut pressures = vec![0.0; (rows * cols) as usize];
 
         for i in 0..rows {
             for j in 0..cols {
@@ -41,112 +103,646 @@ impl FluidSimulation {
                 let jitter_y = (rand::random::<f32>() - 0.5) / 50.0;
 
                 positions.push(Vector2::new(
-                    j as f32 * smoothing_radius * 0.95 + left + jitter_x,
-                    i as f32 * smoothing_radius * 0.95 + top + jitter_y,
+                    j as f32 * params.smoothing_radius * 0.95 + left + jitter_x,
+                    i as f32 * params.smoothing_radius * 0.95 + top + jitter_y,
                 ));
 
                 velocities.push(Vector2::new(0.0, 0.0));
             }
         }
 
+        Self::with_particles(params, positions, velocities)
+    }
+
+    pub fn with_particles(
+        params: SimulationParams,
+        positions: Vec<Vector2<f32>>,
+        velocities: Vec<Vector2<f32>>,
+    ) -> Self {
         Self {
-            smoothing_radius,
-            bound_damping,
-            mass,
-            viscosity,
-            positions,
-            velocities,
-            densities,
-            pressures,
+            params,
+            capacity: positions.len(),
+            particles: Particles::new(positions, velocities),
+            neighbors: NeighborList::new(NeighborSearch::default()),
+            solver: Box::new(Wcsph::default()),
+            boundary: None,
+            obstacles: Vec::new(),
+            rigid_bodies: Vec::new(),
+            emitters: Vec::new(),
+            sinks: Vec::new(),
+            container_motion: Motion::Static,
+            xsph_corrections: Vec::new(),
+            heat_rates: Vec::new(),
+            accumulator: Accumulator::default(),
+            time: 0.0,
+            last_step: StepStats::default(),
+        }
+    }
+
+    pub fn with_solver(mut self, solver: impl Solver + 'static) -> Self {
+        self.solver = Box::new(solver);
+        self
+    }
+
+    pub fn solver(&self) -> &dyn Solver {
+        self.solver.as_ref()
+    }
+
+    pub fn set_solver(&mut self, solver: impl Solver + 'static) {
+        self.solver = Box::new(solver);
+    }
+
+    pub fn with_boundary_particles(mut self, boundary: BoundaryParticles) -> Self {
+        self.set_boundary_particles(Some(boundary));
+        self
+    }
+
+    pub fn boundary_particles(&self) -> Option<&BoundaryParticles> {
+        self.boundary.as_ref()
+    }
+
+    /// Boundary particles replace the wall clamp for solvers that support them.
+    pub fn set_boundary_particles(&mut self, mut boundary: Option<BoundaryParticles>) {
+        if let Some(boundary) = &mut boundary {
+            boundary.update(&self.params);
+        }
+        self.boundary = boundary;
+    }
+
+    pub fn with_obstacle(mut self, obstacle: Obstacle) -> Self {
+        self.obstacles.push(obstacle);
+        self
+    }
+
+    pub fn obstacles(&self) -> &[Obstacle] {
+        &self.obstacles
+    }
+
+    pub fn add_obstacle(&mut self, obstacle: Obstacle) {
+        self.obstacles.push(obstacle);
+    }
+
+    pub fn clear_obstacles(&mut self) {
+        self.obstacles.clear();
+    }
+
+    pub fn with_rigid_body(mut self, body: RigidBody) -> Self {
+        self.add_rigid_body(body);
+        self
+    }
+
+    pub fn rigid_bodies(&self) -> &[RigidBody] {
+        &self.rigid_bodies
+    }
+
+    pub fn rigid_bodies_mut(&mut self) -> &mut [RigidBody] {
+        &mut self.rigid_bodies
+    }
+
+    pub fn add_rigid_body(&mut self, mut body: RigidBody) {
+        body.update_boundary(&self.params);
+        self.rigid_bodies.push(body);
+    }
+
+    pub fn clear_rigid_bodies(&mut self) {
+        self.rigid_bodies.clear();
+    }
+
+    pub fn with_emitter(mut self, emitter: Emitter) -> Result<Self, ParamsError> {
+        self.add_emitter(emitter)?;
+        Ok(self)
+    }
+
+    pub fn emitters(&self) -> &[Emitter] {
+        &self.emitters
+    }
+
+    pub fn emitters_mut(&mut self) -> &mut [Emitter] {
+        &mut self.emitters
+    }
+
+    /// Fails if the emitter's phase isn't defined in the params.
+    pub fn add_emitter(&mut self, emitter: Emitter) -> Result<(), ParamsError> {
+        self.params.check_phase(emitter.phase)?;
+        self.emitters.push(emitter);
+        Ok(())
+    }
+
+    pub fn clear_emitters(&mut self) {
+        self.emitters.clear();
+    }
+
+    pub fn with_sink(mut self, sink: Sink) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    pub fn sinks(&self) -> &[Sink] {
+        &self.sinks
+    }
+
+    pub fn add_sink(&mut self, sink: Sink) {
+        self.sinks.push(sink);
+    }
+
+    pub fn clear_sinks(&mut self) {
+        self.sinks.clear();
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.set_capacity(capacity);
+        self
+    }
+
+    /// Maximum number of particles, emitters pause while it is reached.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Particles beyond a lowered capacity are removed, newest first.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        if self.particles.len() > capacity {
+            let kept = (0..self.particles.len()).map(|i| i < capacity).collect();
+            self.retain_particles(kept);
+        }
+    }
+
+    pub fn container_motion(&self) -> Motion {
+        self.container_motion
+    }
+
+    /// Moves the domain walls and any boundary particles.
+    pub fn set_container_motion(&mut self, motion: Motion) {
+        self.container_motion = motion;
+    }
+
+    /// Pose and velocity of the container at the current time.
+    pub fn container(&self) -> KinematicState {
+        self.container_motion.state(self.time)
+    }
+
+    pub fn params(&self) -> &SimulationParams {
+        &self.params
+    }
+
+    pub fn set_params(&mut self, params: SimulationParams) {
+        if params.time_stepping != self.params.time_stepping {
+            self.accumulator.reset();
+        }
+        if let Some(boundary) = &mut self.boundary {
+            boundary.update(&params);
         }
+        for body in &mut self.rigid_bodies {
+            body.update_boundary(&params);
+        }
+        self.params = params;
+    }
+
+    /// Simulated time in seconds.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn neighbor_search(&self) -> NeighborSearch {
+        self.neighbors.search()
+    }
+
+    pub fn set_neighbor_search(&mut self, search: NeighborSearch) {
+        self.neighbors.set_search(search);
     }
 
     pub fn positions_data(&self) -> &[u8] {
-        let len = self.positions.len() * std::mem::size_of::<Vector2<f32>>();
-        let ptr = self.positions.as_ptr() as *const u8;
+        let len = self.particles.positions.len() * std::mem::size_of::<Vector2<f32>>();
+        let ptr = self.particles.positions.as_ptr() as *const u8;
 
         unsafe { std::slice::from_raw_parts(ptr, len) }
     }
 
     pub fn density_data(&self) -> &[u8] {
-        let len = self.densities.len() * std::mem::size_of::<f32>();
-        let ptr = self.densities.as_ptr() as *const u8;
+        let len = self.particles.densities.len() * std::mem::size_of::<f32>();
+        let ptr = self.particles.densities.as_ptr() as *const u8;
 
         unsafe { std::slice::from_raw_parts(ptr, len) }
     }
 
+    pub fn particles(&self) -> &Particles {
+        &self.particles
+    }
+
     pub fn num_particles(&self) -> u32 {
-        self.positions.len() as u32
-    }
-
-    pub fn update(&mut self, dt: f32) {
-        self.compute_density();
-        // println!("{:?}", self.pressures);
-
-        let spiky_grad: f32 = -10.0 / (PI * self.smoothing_radius.powi(5));
-        let visc_lap: f32 = 40.0 / (PI * self.smoothing_radius.powi(5));
-
-        for i in 0..self.densities.len() {
-            let mut force = Vector2::new(0.0, 0.0);
-            for j in 0..self.densities.len() {
-                if i == j {
-                    continue;
-                }
-
-                let r = self.positions[j] - self.positions[i];
-                let r_norm = r.norm();
-                if r_norm < self.smoothing_radius {
-                    force += r.normalize() * self.mass * (self.pressures[i] + self.pressures[j])
-                        / (2.0 * self.densities[j] + 1e-6)
-                        * spiky_grad
-                        * (self.smoothing_radius - r_norm).powi(2);
-                    force += self.viscosity * self.mass * (self.velocities[j] - self.velocities[i]) / ( self.densities[j] + 1e-6) * visc_lap * (self.smoothing_radius - r_norm);
-                }
-            }
-            force += G * self.densities[i];
+        self.particles.len() as u32
+    }
 
-            self.velocities[i] += force * (DT / (self.densities[i] + 1e-6) );
-            self.positions[i] += self.velocities[i] * DT;
+    pub fn kinetic_energy(&self) -> f32 {
+        self.particles
+            .velocities
+            .iter()
+            .zip(&self.particles.phases)
+            .map(|(velocity, &phase)| 0.5 * self.params.phase(phase).mass * velocity.norm_squared())
+            .sum()
+    }
 
-            if self.positions[i].x - self.smoothing_radius < -1.0 {
-                self.velocities[i].x *= self.bound_damping;
-                self.positions[i].x = -1.0 + self.smoothing_radius;
-            }
+    pub fn potential_energy(&self) -> f32 {
+        self.particles
+            .positions
+            .iter()
+            .zip(&self.particles.phases)
+            .map(|(position, &phase)| {
+                -self.params.phase(phase).mass * self.params.gravity.dot(position)
+            })
+            .sum()
+    }
+
+    /// Sets the phase of every particle from its position, e.g. to layer oil over water.
+    ///
+    /// Leaves the particles untouched if any id isn't defined in the params.
+    pub fn assign_phases(
+        &mut self,
+        phase: impl FnMut(Vector2<f32>) -> u32,
+    ) -> Result<(), ParamsError> {
+        let phases: Vec<u32> = self
+            .particles
+            .positions
+            .iter()
+            .copied()
+            .map(phase)
+            .collect();
+        for &id in &phases {
+            self.params.check_phase(id)?;
+        }
+
+        self.particles.phases = phases;
+        Ok(())
+    }
 
-            if self.positions[i].x + self.smoothing_radius > 1.0 {
-                self.velocities[i].x *= self.bound_damping;
-                self.positions[i].x = 1.0 - self.smoothing_radius;
+    /// Sets the temperature of every particle from its position.
+    pub fn assign_temperatures(&mut self, mut temperature: impl FnMut(Vector2<f32>) -> f32) {
+        for (value, &position) in self
+            .particles
+            .temperatures
+            .iter_mut()
+            .zip(&self.particles.positions)
+        {
+            *value = temperature(position);
+        }
+    }
+
+    /// Render color of every particle from its effective viscosity, blue at
+    /// zero to red at `max_viscosity` and above.
+    pub fn viscosity_colors(&self, max_viscosity: f32) -> Vec<[f32; 3]> {
+        self.particles
+            .viscosities
+            .iter()
+            .map(|&viscosity| {
+                let t = (viscosity / max_viscosity).clamp(0.0, 1.0);
+                [t, 0.2, 1.0 - t]
+            })
+            .collect()
+    }
+
+    /// Render color of every particle, from its phase.
+    pub fn particle_colors(&self) -> Vec<[f32; 3]> {
+        self.particles
+            .phases
+            .iter()
+            .map(|&phase| self.params.phase(phase).color)
+            .collect()
+    }
+
+    pub fn last_step(&self) -> StepStats {
+        self.last_step
+    }
+
+    /// Advances the simulation by `dt` seconds of wall-clock time, returns the number of solver steps taken.
+    pub fn update(&mut self, dt: f32) -> u32 {
+        self.accumulator.add(dt, &self.params.time_stepping);
+
+        let Some(adaptive) = self.params.time_stepping.adaptive else {
+            let steps = self
+                .accumulator
+                .take_fixed_steps(&self.params.time_stepping);
+            let substeps = steps.saturating_mul(self.params.time_stepping.substeps);
+
+            for _ in 0..substeps {
+                self.step(self.params.time_stepping.substep());
             }
 
-            if self.positions[i].y - self.smoothing_radius < -1.0 {
-                self.velocities[i].y *= self.bound_damping;
-                self.positions[i].y = -1.0 + self.smoothing_radius;
+            return substeps;
+        };
+
+        let max_substeps = self.params.time_stepping.max_substeps_per_update();
+        let mut substeps = 0;
+        while substeps < max_substeps && self.accumulator.pending() >= adaptive.min_step {
+            let dt = self
+                .stable_timestep(&adaptive)
+                .min(self.accumulator.pending());
+
+            self.step(dt);
+            self.accumulator.consume(dt);
+            substeps += 1;
+        }
+
+        if substeps == max_substeps {
+            self.accumulator.reset();
+        }
+
+        substeps
+    }
+
+    /// Largest step allowed by the velocity, acceleration and viscosity conditions
+    /// of the last evaluated state, clamped to `[min_step, step / substeps]`.
+    pub fn stable_timestep(&self, adaptive: &AdaptiveTimestep) -> f32 {
+        let max_velocity_sq = self
+            .particles
+            .velocities
+            .iter()
+            .map(|velocity| velocity.norm_squared())
+            .fold(0.0, f32::max);
+        let max_acceleration_sq = self
+            .particles
+            .accelerations
+            .iter()
+            .map(|acceleration| acceleration.norm_squared())
+            .fold(0.0, f32::max);
+
+        let mut dt = self.params.time_stepping.substep();
+        let max_velocity = max_velocity_sq.sqrt();
+        let speed_of_sound = self.params.equation_of_state.speed_of_sound();
+        let mut signal_speed = max_velocity;
+        if self.solver.compressible() {
+            signal_speed += speed_of_sound;
+        }
+        if let Some(artificial_viscosity) = self.params.artificial_viscosity {
+            signal_speed += 1.2
+                * (artificial_viscosity.alpha * speed_of_sound
+                    + artificial_viscosity.beta * max_velocity);
+        }
+        if signal_speed > 0.0 {
+            dt = dt.min(adaptive.cfl_factor * self.params.smoothing_radius / signal_speed);
+        }
+        if max_acceleration_sq > 0.0 {
+            dt = dt.min(
+                adaptive.force_factor
+                    * (self.params.smoothing_radius / max_acceleration_sq.sqrt()).sqrt(),
+            );
+        }
+        let max_viscosity = self
+            .particles
+            .viscosities
+            .iter()
+            .fold(self.params.viscosity, |max, &viscosity| max.max(viscosity));
+        if max_viscosity > 0.0 {
+            dt = dt.min(
+                adaptive.viscosity_factor
+                    * self.params.smoothing_radius
+                    * self.params.smoothing_radius
+                    / max_viscosity,
+            );
+        }
+        if let Some(heat) = self.params.heat.filter(|heat| heat.diffusivity > 0.0) {
+            dt = dt.min(
+                adaptive.viscosity_factor
+                    * self.params.smoothing_radius
+                    * self.params.smoothing_radius
+                    / heat.diffusivity,
+            );
+        }
+
+        dt.max(adaptive.min_step)
+    }
+
+    pub fn step(&mut self, dt: f32) -> StepStats {
+        self.apply_emitters_and_sinks(dt);
+
+        // Walls and obstacles are posed at the end of the step
+        let end_time = self.time + dt;
+        let container = self.container_motion.state(end_time);
+        let use_boundary = self.solver.supports_boundary_particles();
+        if let Some(boundary) = &mut self.boundary {
+            boundary.set_container(container);
+            if use_boundary {
+                boundary.set_rigid_bodies(&self.rigid_bodies);
             }
+        }
+        self.neighbors.set_domain(self.params.domain());
+        let single_phase;
+        let params = if self.solver.supports_phases() || self.params.phases.is_empty() {
+            &self.params
+        } else {
+            single_phase = self.params.single_phase();
+            &single_phase
+        };
+        let mut context = SolverContext {
+            params,
+            particles: &mut self.particles,
+            neighbors: &mut self.neighbors,
+            boundary: self.boundary.as_ref().filter(|_| use_boundary),
+            container,
+        };
+        let stats = self.solver.step(&mut context, dt);
 
-            if self.positions[i].y + self.smoothing_radius > 1.0 {
-                self.velocities[i].y *= self.bound_damping;
-                self.positions[i].y = 1.0 - self.smoothing_radius;
+        if let Some(factor) = self.params.xsph {
+            sph::apply_xsph(
+                &mut self.particles,
+                &self.neighbors,
+                &self.params,
+                factor,
+                &mut self.xsph_corrections,
+            );
+        }
+
+        heat::conduct_heat(
+            &mut self.particles,
+            &self.neighbors,
+            &self.obstacles,
+            &self.params,
+            end_time,
+            dt,
+            &mut self.heat_rates,
+        );
+
+        // Particles keep half a smoothing radius from obstacle and body surfaces
+        let margin = 0.5 * self.params.smoothing_radius;
+        let pressure_kernel = (use_boundary && self.boundary.is_some())
+            .then(|| self.solver.pressure_kernel(&self.params));
+        self.step_rigid_bodies(&container, margin, pressure_kernel, end_time, dt);
+
+        for obstacle in &self.obstacles {
+            let state = obstacle.motion.state(end_time);
+            for (position, velocity) in self
+                .particles
+                .positions
+                .iter_mut()
+                .zip(self.particles.velocities.iter_mut())
+            {
+                obstacle.collide(&state, margin, position, velocity);
             }
         }
+
+        sph::enforce_boundaries(
+            &self.params,
+            &container,
+            self.boundary.as_ref().filter(|_| use_boundary),
+            &mut self.particles.positions,
+            &mut self.particles.velocities,
+        );
+        self.params
+            .domain()
+            .wrap_periodic(&mut self.particles.positions);
+        self.time = end_time;
+
+        self.last_step = StepStats {
+            dt,
+            iterations: stats.iterations,
+            density_error: stats.density_error,
+        };
+        self.last_step
     }
 
-    fn compute_density(&mut self) {
-        let smoothing_radius_sq = self.smoothing_radius * self.smoothing_radius;
-        let poly6: f32 = 4.0 / (PI * self.smoothing_radius.powi(8));
+    fn apply_emitters_and_sinks(&mut self, dt: f32) {
+        if !self.sinks.is_empty() {
+            let kept: Vec<bool> = self
+                .particles
+                .positions
+                .iter()
+                .map(|&position| !self.sinks.iter().any(|sink| sink.contains(position)))
+                .collect();
+            if kept.contains(&false) {
+                self.retain_particles(kept);
+            }
+        }
+
+        for emitter in &mut self.emitters {
+            let budget = self.capacity.saturating_sub(self.particles.len());
+            let (phase, temperature) = (emitter.phase, emitter.temperature);
+            emitter.emit(dt, budget, |position, velocity| {
+                self.particles.push(position, velocity, phase, temperature)
+            });
+        }
+    }
 
-        for i in 0..self.densities.len() {
-            self.densities[i] = 0.0;
+    fn retain_particles(&mut self, kept: Vec<bool>) {
+        let mut keep = kept.iter();
+        self.particles.retain(|_| *keep.next().unwrap());
+        self.solver.retain_particles(&kept);
+    }
 
-            for j in 0..self.densities.len() {
-                let r = self.positions[j] - self.positions[i];
-                let r_sq = r.norm_squared();
+    fn step_rigid_bodies(
+        &mut self,
+        container: &KinematicState,
+        margin: f32,
+        pressure_kernel: Option<SmoothingKernel>,
+        end_time: f32,
+        dt: f32,
+    ) {
+        for body in &mut self.rigid_bodies {
+            if let Some(kernel) = pressure_kernel {
+                body.apply_fluid_pressure(&self.params, &self.particles, kernel, dt);
+            }
+            body.integrate(self.params.gravity, dt);
+            body.collide_particles(&self.params, margin, &mut self.particles);
+        }
 
-                if r_sq < smoothing_radius_sq {
-                    self.densities[i] += self.mass * poly6 * (smoothing_radius_sq - r_sq).powi(3);
-                }
+        for i in 1..self.rigid_bodies.len() {
+            let (head, tail) = self.rigid_bodies.split_at_mut(i);
+            for other in head {
+                RigidBody::collide_bodies(&mut tail[0], other);
             }
+        }
 
-            self.pressures[i] = GAS_CONST * (self.densities[i] - REST_DENS);
+        // Bodies pass through periodic axes, they don't interact across the seam
+        let domain = self.params.domain();
+        let mut half_extents = domain.half_extents;
+        for axis in 0..2 {
+            if domain.boundary_modes[axis] == BoundaryMode::Periodic {
+                half_extents[axis] = f32::INFINITY;
+            }
         }
+        let walls = |point: Vector2<f32>| -rigid_body::box_distance(point, half_extents);
+        for body in &mut self.rigid_bodies {
+            for obstacle in &self.obstacles {
+                body.collide_with_solid(
+                    obstacle.shape.as_ref(),
+                    &obstacle.motion.state(end_time),
+                    (body.restitution * obstacle.restitution).sqrt(),
+                    (body.friction * obstacle.friction).sqrt(),
+                );
+            }
+            body.collide_with_solid(&walls, container, body.restitution, body.friction);
+            domain.wrap_periodic(std::slice::from_mut(&mut body.position));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_phases_are_rejected() {
+        let oil = Phase {
+            rest_density: 0.8,
+            mass: 0.0008,
+            viscosity: 0.002,
+            color: [1.0, 0.8, 0.2],
+        };
+        let params = SimulationParams::builder().phase(oil).build().unwrap();
+        let positions = vec![Vector2::new(-0.5, 0.0), Vector2::new(0.5, 0.0)];
+        let mut simulation =
+            FluidSimulation::with_particles(params, positions, vec![Vector2::zeros(); 2]);
+
+        let result = simulation.assign_phases(|position| if position.x < 0.0 { 1 } else { 2 });
+        assert_eq!(
+            result,
+            Err(ParamsError::UnknownPhase {
+                phase: 2,
+                num_phases: 2
+            })
+        );
+        assert_eq!(simulation.particles().phases, [0, 0]);
+
+        simulation
+            .assign_phases(|position| u32::from(position.x < 0.0))
+            .unwrap();
+        assert_eq!(simulation.particles().phases, [1, 0]);
+
+        let emitter = Emitter::point(Vector2::zeros(), 10.0, Vector2::zeros()).unwrap();
+        assert!(simulation
+            .add_emitter(emitter.clone().with_phase(1))
+            .is_ok());
+        assert!(matches!(
+            simulation.add_emitter(emitter.with_phase(2)),
+            Err(ParamsError::UnknownPhase { phase: 2, .. })
+        ));
+        assert_eq!(simulation.emitters().len(), 1);
+    }
+
+    #[test]
+    fn domain_walls_follow_the_domain_size() {
+        let params = SimulationParams::default();
+        let boundary = BoundaryParticles::domain_walls(&params, 0.02);
+        let mut simulation = FluidSimulation::with_particles(params.clone(), vec![], vec![])
+            .with_boundary_particles(boundary);
+        let extent = |simulation: &FluidSimulation| {
+            let boundary = simulation.boundary_particles().unwrap();
+            boundary
+                .positions()
+                .iter()
+                .fold(Vector2::zeros(), |max: Vector2<f32>, position| {
+                    max.sup(&position.abs())
+                })
+        };
+        assert_eq!(extent(&simulation), Vector2::new(1.0, 1.0));
+
+        let wide = params
+            .to_builder()
+            .domain_size(Vector2::new(3.0, 1.0))
+            .build()
+            .unwrap();
+        simulation.set_params(wide);
+        assert_eq!(extent(&simulation), Vector2::new(1.5, 0.5));
+        let boundary = simulation.boundary_particles().unwrap();
+        assert_eq!(boundary.volumes().len(), boundary.len());
     }
 }
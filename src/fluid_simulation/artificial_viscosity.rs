@@ -0,0 +1,61 @@
+use nalgebra::Vector2;
+
+use super::{Kernel, NeighborList, Particles, SimulationParams};
+
+/// Monaghan 1992 artificial viscosity, only active between approaching particles.
+///
+/// `alpha` scales the linear, shear and bulk viscosity like term, `beta` the
+/// quadratic one that prevents interpenetration at high Mach numbers. The
+/// speed of sound is taken from the equation of state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArtificialViscosity {
+    pub alpha: f32,
+    pub beta: f32,
+}
+
+impl Default for ArtificialViscosity {
+    fn default() -> Self {
+        Self {
+            alpha: 0.1,
+            beta: 0.0,
+        }
+    }
+}
+
+pub(super) fn add_artificial_viscosity_accelerations(
+    particles: &Particles,
+    neighbors: &NeighborList,
+    params: &SimulationParams,
+    accelerations: &mut [Vector2<f32>],
+) {
+    let Some(ArtificialViscosity { alpha, beta }) = params.artificial_viscosity else {
+        return;
+    };
+
+    let smoothing_radius = params.smoothing_radius;
+    let speed_of_sound = params.equation_of_state.speed_of_sound();
+
+    for (i, acceleration) in accelerations.iter_mut().enumerate() {
+        let mut force = Vector2::zeros();
+
+        for (j, image) in neighbors.with_images(i) {
+            let r = particles.positions[i] - image.position(particles.positions[j]);
+            let approach =
+                (particles.velocities[i] - image.vector(particles.velocities[j])).dot(&r);
+            if approach >= 0.0 {
+                continue;
+            }
+
+            let mu = smoothing_radius * approach
+                / (r.norm_squared() + 0.01 * smoothing_radius * smoothing_radius);
+            let mean_density = 0.5 * (particles.densities[i] + particles.densities[j]);
+            let viscosity = (-alpha * speed_of_sound * mu + beta * mu * mu) / mean_density;
+
+            force -= params.phase(particles.phases[j]).mass
+                * viscosity
+                * params.kernels.pressure.gradient(r, smoothing_radius);
+        }
+
+        *acceleration += force;
+    }
+}
@@ -0,0 +1,254 @@
+use nalgebra::Vector2;
+
+use super::{BoundaryMode, Kernel, KinematicState, RigidBody, SimulationParams};
+
+/// Static particles sampled along solid walls (Akinci et al. 2012).
+///
+/// Every boundary particle gets the volume `1 / sum_k W_bk` of its
+/// neighborhood among the other boundary particles, so the sampling density
+/// of a wall doesn't change how strongly it pushes back. Fluid particles treat
+/// a boundary particle as fluid at rest density with that volume and mirror
+/// their own pressure onto it.
+///
+/// Boundary particles are attached to the container and move with it. While
+/// the simulation steps, the particles sampled on rigid bodies are appended
+/// after them, see [`RigidBody`].
+///
+/// Walls from `domain_walls` are resampled whenever the params change, so
+/// they follow the domain size and boundary modes. Particles passed to `new`
+/// are kept as given.
+pub struct BoundaryParticles {
+    num_walls: usize,
+    wall_spacing: Option<f32>,
+    rest_positions: Vec<Vector2<f32>>,
+    positions: Vec<Vector2<f32>>,
+    volumes: Vec<f32>,
+    container: KinematicState,
+
+    origin: Vector2<f32>,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    cell_start: Vec<usize>,
+    cell_entries: Vec<usize>,
+}
+
+impl BoundaryParticles {
+    pub fn new(positions: Vec<Vector2<f32>>, params: &SimulationParams) -> Self {
+        let mut boundary = Self {
+            num_walls: positions.len(),
+            wall_spacing: None,
+            volumes: vec![0.0; positions.len()],
+            rest_positions: positions.clone(),
+            positions,
+            container: KinematicState::default(),
+            origin: Vector2::zeros(),
+            cell_size: 1.0,
+            columns: 0,
+            rows: 0,
+            cell_start: Vec::new(),
+            cell_entries: Vec::new(),
+        };
+        boundary.update(params);
+        boundary
+    }
+
+    /// Samples the walls of the domain with the given particle spacing, only
+    /// on axes with `BoundaryMode::Wall`.
+    pub fn domain_walls(params: &SimulationParams, spacing: f32) -> Self {
+        let mut boundary = Self::new(Vec::new(), params);
+        boundary.wall_spacing = Some(spacing);
+        boundary.update(params);
+        boundary
+    }
+
+    /// World positions for the current container pose.
+    pub fn positions(&self) -> &[Vector2<f32>] {
+        &self.positions
+    }
+
+    /// Positions in the rest frame of the container.
+    pub fn rest_positions(&self) -> &[Vector2<f32>] {
+        &self.rest_positions
+    }
+
+    pub(super) fn set_container(&mut self, container: KinematicState) {
+        if container.transform == self.container.transform {
+            return;
+        }
+
+        self.container = container;
+        for (position, &rest_position) in self.positions.iter_mut().zip(&self.rest_positions) {
+            *position = container.to_world(rest_position);
+        }
+    }
+
+    pub fn volumes(&self) -> &[f32] {
+        &self.volumes
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Replaces the particles of rigid bodies with the current ones of `bodies`.
+    pub(super) fn set_rigid_bodies(&mut self, bodies: &[RigidBody]) {
+        if bodies.is_empty() && self.positions.len() == self.num_walls {
+            return;
+        }
+
+        self.truncate_to_walls();
+        for (position, volume) in bodies.iter().flat_map(RigidBody::boundary_particles) {
+            self.rest_positions.push(self.container.to_local(position));
+            self.positions.push(position);
+            self.volumes.push(volume);
+        }
+        self.rebuild_grid(self.cell_size);
+    }
+
+    /// Rebuilds the lookup grid and volumes for a new smoothing radius or
+    /// kernel, dropping the particles of rigid bodies until the next step.
+    pub fn update(&mut self, params: &SimulationParams) {
+        self.truncate_to_walls();
+        if let Some(spacing) = self.wall_spacing {
+            self.rest_positions = sample_domain_walls(params, spacing);
+            self.positions = self
+                .rest_positions
+                .iter()
+                .map(|&position| self.container.to_world(position))
+                .collect();
+            self.num_walls = self.positions.len();
+            self.volumes.resize(self.num_walls, 0.0);
+        }
+        self.rebuild_grid(params.smoothing_radius);
+
+        for i in 0..self.positions.len() {
+            let position = self.positions[i];
+            let kernel_sum: f32 = self
+                .neighbors(position, params.smoothing_radius)
+                .map(|j| {
+                    params.kernels.density.value(
+                        (position - self.positions[j]).norm(),
+                        params.smoothing_radius,
+                    )
+                })
+                .sum();
+
+            self.volumes[i] = 1.0 / kernel_sum;
+        }
+    }
+
+    /// Boundary particles closer than `radius` to `position`, `radius` must
+    /// not exceed the smoothing radius of the last `update`.
+    pub(super) fn neighbors(
+        &self,
+        position: Vector2<f32>,
+        radius: f32,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let position = self.container.to_local(position);
+        let (cx, cy) = self.cell(position);
+        let radius_sq = radius * radius;
+
+        itertools::iproduct!(-1..=1, -1..=1)
+            .filter_map(move |(dy, dx)| {
+                let x = usize::try_from(cx + dx)
+                    .ok()
+                    .filter(|&x| x < self.columns)?;
+                let y = usize::try_from(cy + dy).ok().filter(|&y| y < self.rows)?;
+                let cell = y * self.columns + x;
+                Some(&self.cell_entries[self.cell_start[cell]..self.cell_start[cell + 1]])
+            })
+            .flatten()
+            .copied()
+            .filter(move |&j| (self.rest_positions[j] - position).norm_squared() < radius_sq)
+    }
+
+    fn truncate_to_walls(&mut self) {
+        self.rest_positions.truncate(self.num_walls);
+        self.positions.truncate(self.num_walls);
+        self.volumes.truncate(self.num_walls);
+    }
+
+    fn rebuild_grid(&mut self, cell_size: f32) {
+        let (min, max) = self.rest_positions.iter().fold(
+            (
+                Vector2::repeat(f32::INFINITY),
+                Vector2::repeat(f32::NEG_INFINITY),
+            ),
+            |(min, max), position| (min.inf(position), max.sup(position)),
+        );
+
+        self.cell_size = cell_size;
+        self.origin = if self.rest_positions.is_empty() {
+            Vector2::zeros()
+        } else {
+            min
+        };
+        self.columns = ((max.x - self.origin.x) / cell_size).floor().max(0.0) as usize + 1;
+        self.rows = ((max.y - self.origin.y) / cell_size).floor().max(0.0) as usize + 1;
+
+        // Counting sort of particle indices by cell
+        let num_cells = self.columns * self.rows;
+        self.cell_start.clear();
+        self.cell_start.resize(num_cells + 1, 0);
+        for &position in &self.rest_positions {
+            let cell = self.cell_index(position);
+            self.cell_start[cell + 1] += 1;
+        }
+        for cell in 0..num_cells {
+            self.cell_start[cell + 1] += self.cell_start[cell];
+        }
+
+        self.cell_entries.clear();
+        self.cell_entries.resize(self.rest_positions.len(), 0);
+        let mut fill = self.cell_start[..num_cells].to_vec();
+        for (i, &position) in self.rest_positions.iter().enumerate() {
+            let cell = self.cell_index(position);
+            self.cell_entries[fill[cell]] = i;
+            fill[cell] += 1;
+        }
+    }
+
+    fn cell(&self, position: Vector2<f32>) -> (isize, isize) {
+        (
+            ((position.x - self.origin.x) / self.cell_size).floor() as isize,
+            ((position.y - self.origin.y) / self.cell_size).floor() as isize,
+        )
+    }
+
+    fn cell_index(&self, position: Vector2<f32>) -> usize {
+        let (cx, cy) = self.cell(position);
+        cy as usize * self.columns + cx as usize
+    }
+}
+
+fn sample_domain_walls(params: &SimulationParams, spacing: f32) -> Vec<Vector2<f32>> {
+    let half_extents = params.domain().half_extents;
+    let counts = half_extents.map(|half_extent| (2.0 * half_extent / spacing).ceil() as usize);
+    let mut positions = Vec::with_capacity(2 * (counts.x + counts.y));
+    let [x_walls, y_walls] = params.boundary_modes.map(|mode| mode == BoundaryMode::Wall);
+    let sample = |axis: usize, k: usize| {
+        -half_extents[axis] + k as f32 * 2.0 * half_extents[axis] / counts[axis] as f32
+    };
+
+    for k in 0..counts.x.max(counts.y) {
+        if y_walls && k < counts.x {
+            positions.push(Vector2::new(sample(0, k), -half_extents.y));
+        }
+        if x_walls && k < counts.y {
+            positions.push(Vector2::new(half_extents.x, sample(1, k)));
+        }
+        if y_walls && k < counts.x {
+            positions.push(Vector2::new(-sample(0, k), half_extents.y));
+        }
+        if x_walls && k < counts.y {
+            positions.push(Vector2::new(-half_extents.x, -sample(1, k)));
+        }
+    }
+
+    positions
+}
@@ -0,0 +1,297 @@
+use nalgebra::Vector2;
+
+use super::{
+    params::{at_least_one, non_negative},
+    rheology, sph, Kernel, NeighborList, ParamsError, Particles, SimulationParams, Solver,
+    SolverContext, SolverStats,
+};
+
+/// Divergence-free SPH (Bender & Koschier 2015).
+///
+/// Each step first removes the velocity divergence at the current positions,
+/// then corrects the predicted density error before advecting particles.
+/// Both tolerances are relative to the rest density, the divergence one per step.
+pub struct Dfsph {
+    tolerance: f32,
+    divergence_tolerance: f32,
+    max_iterations: u32,
+
+    factors: Vec<f32>,
+    stiffness: Vec<f32>,
+    initial_velocities: Vec<Vector2<f32>>,
+    non_pressure_accelerations: Vec<Vector2<f32>>,
+}
+
+impl Dfsph {
+    const MIN_DENSITY_ITERATIONS: u32 = 2;
+    const MIN_DIVERGENCE_ITERATIONS: u32 = 1;
+
+    pub fn new(
+        tolerance: f32,
+        divergence_tolerance: f32,
+        max_iterations: u32,
+    ) -> Result<Self, ParamsError> {
+        non_negative("DFSPH tolerance", tolerance)?;
+        non_negative("DFSPH divergence tolerance", divergence_tolerance)?;
+        at_least_one("DFSPH max iterations", max_iterations)?;
+
+        Ok(Self {
+            tolerance,
+            divergence_tolerance,
+            max_iterations,
+            ..Self::default()
+        })
+    }
+
+    pub fn tolerance(&self) -> f32 {
+        self.tolerance
+    }
+
+    pub fn divergence_tolerance(&self) -> f32 {
+        self.divergence_tolerance
+    }
+
+    /// Limit of each of the divergence and density loops.
+    pub fn max_iterations(&self) -> u32 {
+        self.max_iterations
+    }
+
+    /// `alpha_i = rho_i / (|sum m grad W_ij|^2 + sum |m grad W_ij|^2)`.
+    ///
+    /// All gradients use the density kernel, the Jacobi style update is only
+    /// stable when the system it solves is symmetric.
+    fn compute_factors(
+        &mut self,
+        particles: &Particles,
+        neighbors: &NeighborList,
+        params: &SimulationParams,
+    ) {
+        self.factors.resize(particles.len(), 0.0);
+
+        for (i, factor) in self.factors.iter_mut().enumerate() {
+            let mut gradient_sum = Vector2::zeros();
+            let mut gradient_sq_sum = 0.0;
+
+            for (j, image) in neighbors.with_images(i) {
+                let gradient = params.mass
+                    * params.kernels.density.gradient(
+                        particles.positions[i] - image.position(particles.positions[j]),
+                        params.smoothing_radius,
+                    );
+
+                gradient_sum += gradient;
+                gradient_sq_sum += gradient.norm_squared();
+            }
+
+            let denominator = gradient_sum.norm_squared() + gradient_sq_sum;
+            *factor = if denominator > 1e-6 {
+                particles.densities[i] / denominator
+            } else {
+                0.0
+            };
+        }
+    }
+
+    fn density_change_rate(
+        i: usize,
+        particles: &Particles,
+        neighbors: &NeighborList,
+        params: &SimulationParams,
+    ) -> f32 {
+        neighbors
+            .with_images(i)
+            .map(|(j, image)| {
+                params.mass
+                    * (particles.velocities[i] - image.vector(particles.velocities[j])).dot(
+                        &params.kernels.density.gradient(
+                            particles.positions[i] - image.position(particles.positions[j]),
+                            params.smoothing_radius,
+                        ),
+                    )
+            })
+            .sum()
+    }
+
+    /// Applies `v_i -= dt * sum m (kappa_i / rho_i + kappa_j / rho_j) grad W_ij`
+    /// with `stiffness` holding `kappa / rho`.
+    fn correct_velocities(
+        &self,
+        particles: &mut Particles,
+        neighbors: &NeighborList,
+        params: &SimulationParams,
+        dt: f32,
+    ) {
+        for i in 0..particles.len() {
+            let mut correction = Vector2::zeros();
+            for (j, image) in neighbors.with_images(i) {
+                correction += params.mass
+                    * (self.stiffness[i] + self.stiffness[j])
+                    * params.kernels.density.gradient(
+                        particles.positions[i] - image.position(particles.positions[j]),
+                        params.smoothing_radius,
+                    );
+            }
+
+            particles.velocities[i] -= correction * dt;
+        }
+    }
+
+    fn correct_divergence_error(
+        &mut self,
+        particles: &mut Particles,
+        neighbors: &NeighborList,
+        params: &SimulationParams,
+        dt: f32,
+    ) -> u32 {
+        let mut iterations = 0;
+
+        loop {
+            let mut error = 0.0;
+            for i in 0..particles.len() {
+                let rate = Self::density_change_rate(i, particles, neighbors, params).max(0.0);
+                error += rate;
+                self.stiffness[i] = rate / dt * self.factors[i] / particles.densities[i];
+            }
+            error *= dt / (particles.len().max(1) as f32 * params.rest_density);
+
+            if iterations >= self.max_iterations
+                || (iterations >= Self::MIN_DIVERGENCE_ITERATIONS
+                    && error <= self.divergence_tolerance)
+            {
+                return iterations;
+            }
+
+            self.correct_velocities(particles, neighbors, params, dt);
+            iterations += 1;
+        }
+    }
+
+    fn correct_density_error(
+        &mut self,
+        particles: &mut Particles,
+        neighbors: &NeighborList,
+        params: &SimulationParams,
+        dt: f32,
+    ) -> (u32, f32) {
+        let mut iterations = 0;
+
+        loop {
+            let mut error = 0.0;
+            for i in 0..particles.len() {
+                let predicted_density = (particles.densities[i]
+                    + dt * Self::density_change_rate(i, particles, neighbors, params))
+                .max(params.rest_density);
+                error += predicted_density - params.rest_density;
+                self.stiffness[i] = (predicted_density - params.rest_density) / (dt * dt)
+                    * self.factors[i]
+                    / particles.densities[i];
+            }
+            error /= particles.len().max(1) as f32 * params.rest_density;
+
+            if iterations >= self.max_iterations
+                || (iterations >= Self::MIN_DENSITY_ITERATIONS && error <= self.tolerance)
+            {
+                return (iterations, error);
+            }
+
+            self.correct_velocities(particles, neighbors, params, dt);
+            iterations += 1;
+        }
+    }
+}
+
+impl Default for Dfsph {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.01,
+            divergence_tolerance: 0.01,
+            max_iterations: 100,
+            factors: Vec::new(),
+            stiffness: Vec::new(),
+            initial_velocities: Vec::new(),
+            non_pressure_accelerations: Vec::new(),
+        }
+    }
+}
+
+impl Solver for Dfsph {
+    fn name(&self) -> &'static str {
+        "DFSPH"
+    }
+
+    fn step(&mut self, context: &mut SolverContext, dt: f32) -> SolverStats {
+        let params = context.params;
+        let particles = &mut *context.particles;
+        let neighbors = &mut *context.neighbors;
+        let num_particles = particles.len();
+
+        self.initial_velocities.clone_from(&particles.velocities);
+        self.stiffness.resize(num_particles, 0.0);
+        self.non_pressure_accelerations
+            .resize(num_particles, Vector2::zeros());
+
+        neighbors.rebuild(&particles.positions, params.smoothing_radius);
+        sph::compute_densities(
+            &particles.positions,
+            &particles.phases,
+            neighbors,
+            None,
+            params,
+            &mut particles.densities,
+        );
+        self.compute_factors(particles, neighbors, params);
+
+        let divergence_iterations = self.correct_divergence_error(particles, neighbors, params, dt);
+
+        rheology::compute_viscosities(particles, neighbors, params);
+        sph::non_pressure_accelerations(
+            particles,
+            neighbors,
+            params,
+            &mut self.non_pressure_accelerations,
+        );
+        for (velocity, acceleration) in particles
+            .velocities
+            .iter_mut()
+            .zip(&self.non_pressure_accelerations)
+        {
+            *velocity += acceleration * dt;
+        }
+
+        let (density_iterations, density_error) =
+            self.correct_density_error(particles, neighbors, params, dt);
+
+        for i in 0..num_particles {
+            particles.positions[i] += particles.velocities[i] * dt;
+            particles.accelerations[i] =
+                (particles.velocities[i] - self.initial_velocities[i]) / dt;
+        }
+
+        SolverStats {
+            iterations: divergence_iterations + density_iterations,
+            density_error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_parameters_are_rejected() {
+        assert!(matches!(
+            Dfsph::new(f32::NAN, 0.01, 100),
+            Err(ParamsError::NotFinite("DFSPH tolerance"))
+        ));
+        assert!(matches!(
+            Dfsph::new(0.01, -0.01, 100),
+            Err(ParamsError::Negative("DFSPH divergence tolerance", _))
+        ));
+        assert_eq!(
+            Dfsph::new(0.01, 0.01, 0).err(),
+            Some(ParamsError::Zero("DFSPH max iterations"))
+        );
+        assert!(Dfsph::new(0.01, 0.01, 1).is_ok());
+    }
+}
@@ -0,0 +1,135 @@
+use nalgebra::Vector2;
+
+/// Treatment of one axis of the domain.
+///
+/// Periodic and mirror axes are meant for a static container, they always
+/// refer to the domain at rest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BoundaryMode {
+    /// Solid walls, particles are clamped and damped by `bound_damping`.
+    #[default]
+    Wall,
+    /// Particles leaving through one side enter through the other, neighbors
+    /// are found across the seam.
+    Periodic,
+    /// Free-slip walls. Particles near a wall see mirror images of their
+    /// neighbors behind it, so the fluid isn't thinned out at the wall, and
+    /// are reflected without losing tangential velocity.
+    Mirror,
+}
+
+/// Box centered at the origin that contains the fluid, in world units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Domain {
+    pub half_extents: Vector2<f32>,
+    pub boundary_modes: [BoundaryMode; 2],
+}
+
+impl Domain {
+    pub fn size(&self) -> Vector2<f32> {
+        2.0 * self.half_extents
+    }
+
+    /// Moves particles that left through a periodic axis back in on the opposite side.
+    pub(super) fn wrap_periodic(&self, positions: &mut [Vector2<f32>]) {
+        for position in positions {
+            for axis in 0..2 {
+                if self.boundary_modes[axis] == BoundaryMode::Periodic {
+                    let half_extent = self.half_extents[axis];
+                    position[axis] =
+                        (position[axis] + half_extent).rem_euclid(2.0 * half_extent) - half_extent;
+                }
+            }
+        }
+    }
+}
+
+impl Default for Domain {
+    fn default() -> Self {
+        Self {
+            half_extents: Vector2::new(1.0, 1.0),
+            boundary_modes: [BoundaryMode::Wall; 2],
+        }
+    }
+}
+
+/// Copy of a neighbor as seen across a periodic seam or mirror wall, the
+/// position `sign * x + shift` per component.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Image {
+    sign: Vector2<f32>,
+    shift: Vector2<f32>,
+}
+
+impl Image {
+    pub const IDENTITY: Self = Self {
+        sign: Vector2::new(1.0, 1.0),
+        shift: Vector2::new(0.0, 0.0),
+    };
+
+    pub fn position(&self, position: Vector2<f32>) -> Vector2<f32> {
+        self.sign.component_mul(&position) + self.shift
+    }
+
+    /// Maps velocities and other vectors, mirroring is its own inverse so this
+    /// also maps vectors of the image back to the original.
+    pub fn vector(&self, vector: Vector2<f32>) -> Vector2<f32> {
+        self.sign.component_mul(&vector)
+    }
+
+    pub fn is_mirrored(&self) -> bool {
+        self.sign != Self::IDENTITY.sign
+    }
+
+    /// Images of `other` that particles at `position` interact with: the
+    /// nearest periodic copy combined with the mirror images behind the walls
+    /// closest to `position`. `include_identity` is false for the particle's
+    /// own images.
+    pub(super) fn candidates(
+        domain: &Domain,
+        position: Vector2<f32>,
+        other: Vector2<f32>,
+        include_identity: bool,
+        mut visit: impl FnMut(Image),
+    ) {
+        let nearest = Self::nearest(domain, position, other);
+        let mirror = |axis: usize| {
+            let wall = domain.half_extents[axis].copysign(position[axis]);
+            (-1.0, 2.0 * wall)
+        };
+
+        let options = |axis: usize| match domain.boundary_modes[axis] {
+            BoundaryMode::Mirror => [Some((1.0, 0.0)), Some(mirror(axis))],
+            _ => [Some((nearest.sign[axis], nearest.shift[axis])), None],
+        };
+
+        for (sign_y, shift_y) in options(1).into_iter().flatten() {
+            for (sign_x, shift_x) in options(0).into_iter().flatten() {
+                let image = Self {
+                    sign: Vector2::new(sign_x, sign_y),
+                    shift: Vector2::new(shift_x, shift_y),
+                };
+                if include_identity || image.is_mirrored() {
+                    visit(image);
+                }
+            }
+        }
+    }
+
+    /// Periodic copy of `other` closest to `position`, the identity without periodic axes.
+    pub(super) fn nearest(domain: &Domain, position: Vector2<f32>, other: Vector2<f32>) -> Self {
+        let period = domain.size();
+        let mut shift = Vector2::zeros();
+        for axis in 0..2 {
+            if domain.boundary_modes[axis] == BoundaryMode::Periodic {
+                shift[axis] =
+                    period[axis] * ((position[axis] - other[axis]) / period[axis]).round();
+            }
+        }
+
+        Self {
+            shift,
+            ..Self::IDENTITY
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use nalgebra::Vector2;
+
+use super::{params::non_negative, ParamsError, SignedDistance};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmitterShape {
+    Point(Vector2<f32>),
+    /// Particles leave from random points along the segment.
+    Line {
+        start: Vector2<f32>,
+        end: Vector2<f32>,
+    },
+}
+
+/// Nozzle adding `rate` particles per second with the given initial velocity.
+///
+/// Particles emitted during a step are advanced by the time since their
+/// emission, so a point nozzle produces a stream rather than a pile of
+/// coincident particles. `jitter` is the largest random offset of an emitted
+/// particle in each direction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Emitter {
+    pub(super) shape: EmitterShape,
+    pub(super) rate: f32,
+    pub(super) velocity: Vector2<f32>,
+    pub(super) jitter: f32,
+    pub(super) phase: u32,
+    pub(super) temperature: f32,
+
+    pending: f32,
+}
+
+impl Emitter {
+    pub fn new(
+        shape: EmitterShape,
+        rate: f32,
+        velocity: Vector2<f32>,
+    ) -> Result<Self, ParamsError> {
+        let points = match shape {
+            EmitterShape::Point(position) => [position, position],
+            EmitterShape::Line { start, end } => [start, end],
+        };
+        if !points.iter().flatten().all(|x| x.is_finite()) {
+            return Err(ParamsError::NotFinite("emitter position"));
+        }
+        if !velocity.iter().all(|x| x.is_finite()) {
+            return Err(ParamsError::NotFinite("emitter velocity"));
+        }
+        non_negative("emission rate", rate)?;
+
+        Ok(Self {
+            shape,
+            rate,
+            velocity,
+            jitter: 0.0,
+            phase: 0,
+            temperature: 0.0,
+            pending: 0.0,
+        })
+    }
+
+    pub fn point(
+        position: Vector2<f32>,
+        rate: f32,
+        velocity: Vector2<f32>,
+    ) -> Result<Self, ParamsError> {
+        Self::new(EmitterShape::Point(position), rate, velocity)
+    }
+
+    pub fn line(
+        start: Vector2<f32>,
+        end: Vector2<f32>,
+        rate: f32,
+        velocity: Vector2<f32>,
+    ) -> Result<Self, ParamsError> {
+        Self::new(EmitterShape::Line { start, end }, rate, velocity)
+    }
+
+    pub fn with_jitter(mut self, jitter: f32) -> Result<Self, ParamsError> {
+        non_negative("emitter jitter", jitter)?;
+        self.jitter = jitter;
+        Ok(self)
+    }
+
+    /// The phase is checked against the params when the emitter is added to a simulation.
+    pub fn with_phase(mut self, phase: u32) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn shape(&self) -> EmitterShape {
+        self.shape
+    }
+
+    /// Particles per second.
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    pub fn velocity(&self) -> Vector2<f32> {
+        self.velocity
+    }
+
+    pub fn jitter(&self) -> f32 {
+        self.jitter
+    }
+
+    /// Phase id of emitted particles.
+    pub fn phase(&self) -> u32 {
+        self.phase
+    }
+
+    pub fn temperature(&self) -> f32 {
+        self.temperature
+    }
+
+    /// Calls `spawn` with the position and velocity of every particle emitted
+    /// during a step of `dt`, at most `budget` times. Particles that didn't fit
+    /// into the budget are dropped.
+    pub(super) fn emit(
+        &mut self,
+        dt: f32,
+        budget: usize,
+        mut spawn: impl FnMut(Vector2<f32>, Vector2<f32>),
+    ) {
+        self.pending += self.rate * dt;
+        let count = self.pending.floor();
+        self.pending -= count;
+
+        for k in 0..(count as usize).min(budget) {
+            let origin = match self.shape {
+                EmitterShape::Point(position) => position,
+                EmitterShape::Line { start, end } => start + (end - start) * rand::random::<f32>(),
+            };
+            let offset = Vector2::new(rand::random::<f32>(), rand::random::<f32>())
+                .map(|x| (2.0 * x - 1.0) * self.jitter);
+            let age = (self.pending + k as f32) / self.rate;
+
+            spawn(origin + offset + self.velocity * age, self.velocity);
+        }
+    }
+}
+
+/// Region removing every particle that enters it.
+pub struct Sink {
+    region: Box<dyn SignedDistance>,
+}
+
+impl Sink {
+    pub fn new(region: impl SignedDistance + 'static) -> Self {
+        Self {
+            region: Box::new(region),
+        }
+    }
+
+    pub fn region(&self) -> &dyn SignedDistance {
+        self.region.as_ref()
+    }
+
+    pub(super) fn contains(&self, position: Vector2<f32>) -> bool {
+        self.region.signed_distance(position) < 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_emitters_are_rejected() {
+        let position = Vector2::zeros();
+        let velocity = -Vector2::y();
+
+        assert!(matches!(
+            Emitter::point(position, -1.0, velocity),
+            Err(ParamsError::Negative("emission rate", _))
+        ));
+        assert_eq!(
+            Emitter::line(position, Vector2::new(f32::INFINITY, 0.0), 10.0, velocity),
+            Err(ParamsError::NotFinite("emitter position"))
+        );
+        assert_eq!(
+            Emitter::point(position, 10.0, Vector2::new(f32::NAN, 0.0)),
+            Err(ParamsError::NotFinite("emitter velocity"))
+        );
+        assert!(matches!(
+            Emitter::point(position, 10.0, velocity).and_then(|emitter| emitter.with_jitter(-0.1)),
+            Err(ParamsError::Negative("emitter jitter", _))
+        ));
+        assert!(Emitter::point(position, 10.0, velocity)
+            .and_then(|emitter| emitter.with_jitter(0.01))
+            .is_ok());
+    }
+}
@@ -0,0 +1,45 @@
+/// Relation between density and pressure used by the weakly compressible solver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EquationOfState {
+    /// Ideal gas like law `p = stiffness * (rho - rho_0)`.
+    Linear { stiffness: f32 },
+    /// Tait/Cole equation `p = B * ((rho / rho_0)^exponent - 1)` with
+    /// `B = rho_0 * speed_of_sound^2 / exponent`.
+    Tait { speed_of_sound: f32, exponent: f32 },
+}
+
+impl EquationOfState {
+    pub fn tait(speed_of_sound: f32) -> Self {
+        Self::Tait {
+            speed_of_sound,
+            exponent: 7.0,
+        }
+    }
+
+    pub fn pressure(&self, density: f32, rest_density: f32) -> f32 {
+        match *self {
+            Self::Linear { stiffness } => stiffness * (density - rest_density),
+            Self::Tait {
+                speed_of_sound,
+                exponent,
+            } => {
+                let stiffness = rest_density * speed_of_sound * speed_of_sound / exponent;
+                stiffness * ((density / rest_density).powf(exponent) - 1.0)
+            }
+        }
+    }
+
+    /// Speed of sound at rest density, `sqrt(dp / drho)`.
+    pub fn speed_of_sound(&self) -> f32 {
+        match *self {
+            Self::Linear { stiffness } => stiffness.sqrt(),
+            Self::Tait { speed_of_sound, .. } => speed_of_sound,
+        }
+    }
+}
+
+impl Default for EquationOfState {
+    fn default() -> Self {
+        Self::Linear { stiffness: 10.0 }
+    }
+}
@@ -0,0 +1,93 @@
+use nalgebra::Vector2;
+
+use super::{Kernel, NeighborList, Obstacle, Particles, SimulationParams};
+
+/// Heat conduction between particles and Boussinesq buoyancy.
+///
+/// Temperatures are offsets from the ambient temperature at which the fluid
+/// has its rest density. Instead of changing densities, temperature scales
+/// gravity by `1 - expansion * T`, so hot fluid rises and cold fluid sinks.
+///
+/// `diffusivity` is the thermal diffusivity in area per second. Obstacles with
+/// a temperature exchange heat with particles within a smoothing radius of
+/// their surface, relaxing them towards it at `surface_transfer` per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Heat {
+    pub diffusivity: f32,
+    pub expansion: f32,
+    pub surface_transfer: f32,
+}
+
+impl Default for Heat {
+    fn default() -> Self {
+        Self {
+            diffusivity: 0.001,
+            expansion: 0.1,
+            surface_transfer: 5.0,
+        }
+    }
+}
+
+/// Gravity acting on a particle at `temperature`.
+pub(super) fn buoyant_gravity(params: &SimulationParams, temperature: f32) -> Vector2<f32> {
+    match params.heat {
+        Some(heat) => params.gravity * (1.0 - heat.expansion * temperature),
+        None => params.gravity,
+    }
+}
+
+/// Explicit step of `dT_i/dt = sum_j 2 alpha m_j / rho_ij (T_i - T_j) r_ij . grad W_ij / |r_ij|^2`
+/// (Brookshaw 1985) with the mean density `rho_ij`, which conserves heat
+/// between particles, followed by the exchange with heated obstacles.
+pub(super) fn conduct_heat(
+    particles: &mut Particles,
+    neighbors: &NeighborList,
+    obstacles: &[Obstacle],
+    params: &SimulationParams,
+    time: f32,
+    dt: f32,
+    rates: &mut Vec<f32>,
+) {
+    let Some(heat) = params.heat else {
+        return;
+    };
+
+    let smoothing_radius = params.smoothing_radius;
+    let temperatures = &particles.temperatures;
+
+    rates.clear();
+    rates.extend((0..particles.len()).map(|i| {
+        let mut rate = 0.0;
+        for (j, image) in neighbors.with_images(i) {
+            let r = particles.positions[i] - image.position(particles.positions[j]);
+            let gradient = params.kernels.density.gradient(r, smoothing_radius);
+            rate += 4.0 * heat.diffusivity * params.phase(particles.phases[j]).mass
+                / (particles.densities[i] + particles.densities[j] + 1e-6)
+                * (temperatures[i] - temperatures[j])
+                * r.dot(&gradient)
+                / (r.norm_squared() + 0.01 * smoothing_radius * smoothing_radius);
+        }
+        rate
+    }));
+
+    for obstacle in obstacles {
+        let Some(temperature) = obstacle.temperature else {
+            continue;
+        };
+
+        let state = obstacle.motion.state(time);
+        for (i, rate) in rates.iter_mut().enumerate() {
+            let local = state.to_local(particles.positions[i]);
+            let distance = obstacle.shape.signed_distance(local).max(0.0);
+            if distance < smoothing_radius {
+                *rate += heat.surface_transfer
+                    * (1.0 - distance / smoothing_radius)
+                    * (temperature - temperatures[i]);
+            }
+        }
+    }
+
+    for (temperature, rate) in particles.temperatures.iter_mut().zip(rates.iter()) {
+        *temperature += rate * dt;
+    }
+}
@@ -0,0 +1,282 @@
+use nalgebra::Vector2;
+
+use super::{
+    params::{at_least_one, in_range, non_negative},
+    rheology, sph, Image, Kernel, ParamsError, Solver, SolverContext, SolverStats,
+};
+
+/// Implicit incompressible SPH (Ihmsen et al. 2014).
+///
+/// Solves the pressure Poisson equation with relaxed Jacobi iterations until
+/// the average relative compression drops below `tolerance`. Pressures of the
+/// previous step, halved, are used as the initial guess.
+pub struct Iisph {
+    tolerance: f32,
+    max_iterations: u32,
+    relaxation: f32,
+
+    advected_velocities: Vec<Vector2<f32>>,
+    advected_densities: Vec<f32>,
+    diagonal_displacements: Vec<Vector2<f32>>,
+    diagonals: Vec<f32>,
+    pressure_displacements: Vec<Vector2<f32>>,
+    next_pressures: Vec<f32>,
+}
+
+impl Iisph {
+    const MIN_ITERATIONS: u32 = 2;
+
+    pub fn new(tolerance: f32, max_iterations: u32) -> Result<Self, ParamsError> {
+        non_negative("IISPH tolerance", tolerance)?;
+        at_least_one("IISPH max iterations", max_iterations)?;
+
+        Ok(Self {
+            tolerance,
+            max_iterations,
+            ..Self::default()
+        })
+    }
+
+    /// Weight of the Jacobi update, in `(0, 1]`. Smaller values converge
+    /// more slowly but more robustly.
+    pub fn with_relaxation(mut self, relaxation: f32) -> Result<Self, ParamsError> {
+        in_range("IISPH relaxation", relaxation, 0.0, 1.0)?;
+        if relaxation == 0.0 {
+            return Err(ParamsError::NotPositive("IISPH relaxation", relaxation));
+        }
+        self.relaxation = relaxation;
+        Ok(self)
+    }
+
+    pub fn tolerance(&self) -> f32 {
+        self.tolerance
+    }
+
+    pub fn max_iterations(&self) -> u32 {
+        self.max_iterations
+    }
+
+    pub fn relaxation(&self) -> f32 {
+        self.relaxation
+    }
+}
+
+impl Default for Iisph {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.01,
+            max_iterations: 100,
+            relaxation: 0.5,
+            advected_velocities: Vec::new(),
+            advected_densities: Vec::new(),
+            diagonal_displacements: Vec::new(),
+            diagonals: Vec::new(),
+            pressure_displacements: Vec::new(),
+            next_pressures: Vec::new(),
+        }
+    }
+}
+
+impl Solver for Iisph {
+    fn name(&self) -> &'static str {
+        "IISPH"
+    }
+
+    fn step(&mut self, context: &mut SolverContext, dt: f32) -> SolverStats {
+        let params = context.params;
+        let particles = &mut *context.particles;
+        let neighbors = &mut *context.neighbors;
+        let num_particles = particles.len();
+        let mass = params.mass;
+        let gradient = |i: usize, j: usize, image: Image, positions: &[Vector2<f32>]| {
+            params.kernels.density.gradient(
+                positions[i] - image.position(positions[j]),
+                params.smoothing_radius,
+            )
+        };
+
+        neighbors.rebuild(&particles.positions, params.smoothing_radius);
+        sph::compute_densities(
+            &particles.positions,
+            &particles.phases,
+            neighbors,
+            None,
+            params,
+            &mut particles.densities,
+        );
+
+        self.advected_velocities
+            .resize(num_particles, Vector2::zeros());
+        rheology::compute_viscosities(particles, neighbors, params);
+        sph::non_pressure_accelerations(
+            particles,
+            neighbors,
+            params,
+            &mut self.advected_velocities,
+        );
+        particles
+            .accelerations
+            .clone_from(&self.advected_velocities);
+        for (velocity, &initial) in self
+            .advected_velocities
+            .iter_mut()
+            .zip(&particles.velocities)
+        {
+            *velocity = initial + *velocity * dt;
+        }
+
+        // d_ii = -dt^2 sum_j m / rho_i^2 grad W_ij, displacement of i due to its own pressure
+        self.diagonal_displacements
+            .resize(num_particles, Vector2::zeros());
+        self.advected_densities.resize(num_particles, 0.0);
+        for i in 0..num_particles {
+            let mut displacement = Vector2::zeros();
+            let mut density_change = 0.0;
+            for (j, image) in neighbors.with_images(i) {
+                let gradient = gradient(i, j, image, &particles.positions);
+                displacement -= mass * gradient;
+                density_change += mass
+                    * (self.advected_velocities[i] - image.vector(self.advected_velocities[j]))
+                        .dot(&gradient);
+            }
+
+            self.diagonal_displacements[i] =
+                displacement * (dt * dt / (particles.densities[i] * particles.densities[i]));
+            self.advected_densities[i] = particles.densities[i] + dt * density_change;
+        }
+
+        // a_ii = sum_j m (d_ii - d_ji) . grad W_ij
+        self.diagonals.resize(num_particles, 0.0);
+        for i in 0..num_particles {
+            let density_sq = particles.densities[i] * particles.densities[i];
+            self.diagonals[i] = neighbors
+                .with_images(i)
+                .map(|(j, image)| {
+                    let gradient = gradient(i, j, image, &particles.positions);
+                    let displacement_ji = gradient * (dt * dt * mass / density_sq);
+                    mass * (self.diagonal_displacements[i] - displacement_ji).dot(&gradient)
+                })
+                .sum();
+        }
+
+        for pressure in particles.pressures.iter_mut() {
+            *pressure *= 0.5;
+        }
+
+        self.pressure_displacements
+            .resize(num_particles, Vector2::zeros());
+        self.next_pressures.resize(num_particles, 0.0);
+        let mut iterations = 0;
+        let mut density_error;
+
+        loop {
+            // sum_j d_ij p_j = -dt^2 sum_j m p_j / rho_j^2 grad W_ij
+            for i in 0..num_particles {
+                let mut displacement = Vector2::zeros();
+                for (j, image) in neighbors.with_images(i) {
+                    displacement -= mass * particles.pressures[j]
+                        / (particles.densities[j] * particles.densities[j])
+                        * gradient(i, j, image, &particles.positions);
+                }
+                self.pressure_displacements[i] = displacement * (dt * dt);
+            }
+
+            density_error = 0.0;
+            for i in 0..num_particles {
+                let pressure = particles.pressures[i];
+                let density_sq = particles.densities[i] * particles.densities[i];
+
+                let mut off_diagonal = 0.0;
+                for (j, image) in neighbors.with_images(i) {
+                    let gradient = gradient(i, j, image, &particles.positions);
+                    let displacement_ji = gradient * (dt * dt * mass / density_sq);
+
+                    off_diagonal += mass
+                        * (self.pressure_displacements[i]
+                            - image.vector(self.diagonal_displacements[j])
+                                * particles.pressures[j]
+                            - (image.vector(self.pressure_displacements[j])
+                                - displacement_ji * pressure))
+                            .dot(&gradient);
+                }
+
+                let predicted_density =
+                    self.advected_densities[i] + self.diagonals[i] * pressure + off_diagonal;
+                density_error += (predicted_density - params.rest_density).max(0.0);
+
+                self.next_pressures[i] = if self.diagonals[i].abs() > f32::EPSILON {
+                    ((1.0 - self.relaxation) * pressure
+                        + self.relaxation / self.diagonals[i]
+                            * (params.rest_density - self.advected_densities[i] - off_diagonal))
+                        .max(0.0)
+                } else {
+                    0.0
+                };
+            }
+            density_error /= num_particles.max(1) as f32 * params.rest_density;
+
+            std::mem::swap(&mut particles.pressures, &mut self.next_pressures);
+            iterations += 1;
+
+            if iterations >= self.max_iterations
+                || (iterations >= Self::MIN_ITERATIONS && density_error <= self.tolerance)
+            {
+                break;
+            }
+        }
+
+        for i in 0..num_particles {
+            let pressure_term =
+                particles.pressures[i] / (particles.densities[i] * particles.densities[i]);
+
+            let mut acceleration = Vector2::zeros();
+            for (j, image) in neighbors.with_images(i) {
+                acceleration -= mass
+                    * (pressure_term
+                        + particles.pressures[j]
+                            / (particles.densities[j] * particles.densities[j]))
+                    * gradient(i, j, image, &particles.positions);
+            }
+
+            particles.accelerations[i] += acceleration;
+            particles.velocities[i] = self.advected_velocities[i] + acceleration * dt;
+            particles.positions[i] += particles.velocities[i] * dt;
+        }
+
+        SolverStats {
+            iterations,
+            density_error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_parameters_are_rejected() {
+        assert!(matches!(
+            Iisph::new(-0.01, 100),
+            Err(ParamsError::Negative("IISPH tolerance", _))
+        ));
+        assert_eq!(
+            Iisph::new(0.01, 0).err(),
+            Some(ParamsError::Zero("IISPH max iterations"))
+        );
+        assert!(matches!(
+            Iisph::default().with_relaxation(0.0),
+            Err(ParamsError::NotPositive("IISPH relaxation", _))
+        ));
+        assert!(matches!(
+            Iisph::default().with_relaxation(1.5),
+            Err(ParamsError::OutOfRange {
+                name: "IISPH relaxation",
+                ..
+            })
+        ));
+        assert!(Iisph::new(0.01, 100)
+            .and_then(|solver| solver.with_relaxation(1.0))
+            .is_ok());
+    }
+}
@@ -0,0 +1,143 @@
+use nalgebra::Vector2;
+
+use super::Particles;
+
+/// Recomputes `accelerations` (and any derived per particle quantities) from
+/// the current `positions` and `velocities`.
+pub type AccelerationFn<'a> = dyn FnMut(&mut Particles) + 'a;
+
+pub trait Integrator {
+    fn name(&self) -> &'static str;
+
+    fn step(&mut self, particles: &mut Particles, dt: f32, accelerations: &mut AccelerationFn);
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SymplecticEuler;
+
+impl Integrator for SymplecticEuler {
+    fn name(&self) -> &'static str {
+        "symplectic Euler"
+    }
+
+    fn step(&mut self, particles: &mut Particles, dt: f32, accelerations: &mut AccelerationFn) {
+        accelerations(particles);
+
+        for i in 0..particles.len() {
+            particles.velocities[i] += particles.accelerations[i] * dt;
+            particles.positions[i] += particles.velocities[i] * dt;
+        }
+    }
+}
+
+/// Kick-drift-kick leapfrog, the closing kick uses forces evaluated with the half step velocities.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Leapfrog;
+
+impl Integrator for Leapfrog {
+    fn name(&self) -> &'static str {
+        "leapfrog"
+    }
+
+    fn step(&mut self, particles: &mut Particles, dt: f32, accelerations: &mut AccelerationFn) {
+        accelerations(particles);
+
+        for i in 0..particles.len() {
+            particles.velocities[i] += particles.accelerations[i] * (0.5 * dt);
+            particles.positions[i] += particles.velocities[i] * dt;
+        }
+
+        accelerations(particles);
+
+        for i in 0..particles.len() {
+            particles.velocities[i] += particles.accelerations[i] * (0.5 * dt);
+        }
+    }
+}
+
+/// Velocity Verlet, the new forces are evaluated with an explicitly predicted velocity.
+#[derive(Debug, Clone, Default)]
+pub struct VelocityVerlet {
+    velocities: Vec<Vector2<f32>>,
+    accelerations: Vec<Vector2<f32>>,
+}
+
+impl Integrator for VelocityVerlet {
+    fn name(&self) -> &'static str {
+        "velocity Verlet"
+    }
+
+    fn step(&mut self, particles: &mut Particles, dt: f32, accelerations: &mut AccelerationFn) {
+        accelerations(particles);
+
+        self.velocities.clone_from(&particles.velocities);
+        self.accelerations.clone_from(&particles.accelerations);
+
+        for i in 0..particles.len() {
+            particles.positions[i] +=
+                self.velocities[i] * dt + self.accelerations[i] * (0.5 * dt * dt);
+            particles.velocities[i] = self.velocities[i] + self.accelerations[i] * dt;
+        }
+
+        accelerations(particles);
+
+        for i in 0..particles.len() {
+            particles.velocities[i] = self.velocities[i]
+                + (self.accelerations[i] + particles.accelerations[i]) * (0.5 * dt);
+        }
+    }
+}
+
+/// Classic fourth order Runge-Kutta, four force evaluations per step.
+#[derive(Debug, Clone, Default)]
+pub struct RungeKutta4 {
+    positions: Vec<Vector2<f32>>,
+    velocities: Vec<Vector2<f32>>,
+    position_increments: Vec<Vector2<f32>>,
+    velocity_increments: Vec<Vector2<f32>>,
+}
+
+impl Integrator for RungeKutta4 {
+    fn name(&self) -> &'static str {
+        "RK4"
+    }
+
+    fn step(&mut self, particles: &mut Particles, dt: f32, accelerations: &mut AccelerationFn) {
+        let num_particles = particles.len();
+
+        self.positions.clone_from(&particles.positions);
+        self.velocities.clone_from(&particles.velocities);
+        self.position_increments.clear();
+        self.position_increments
+            .resize(num_particles, Vector2::zeros());
+        self.velocity_increments.clear();
+        self.velocity_increments
+            .resize(num_particles, Vector2::zeros());
+
+        accelerations(particles);
+
+        // (weight of the stage just evaluated, offset of the next stage)
+        for (weight, offset) in [(1.0 / 6.0, 0.5), (1.0 / 3.0, 0.5), (1.0 / 3.0, 1.0)] {
+            for i in 0..num_particles {
+                let velocity = particles.velocities[i];
+                let acceleration = particles.accelerations[i];
+
+                self.position_increments[i] += velocity * weight;
+                self.velocity_increments[i] += acceleration * weight;
+
+                particles.positions[i] = self.positions[i] + velocity * (offset * dt);
+                particles.velocities[i] = self.velocities[i] + acceleration * (offset * dt);
+            }
+
+            accelerations(particles);
+        }
+
+        for i in 0..num_particles {
+            self.position_increments[i] += particles.velocities[i] / 6.0;
+            self.velocity_increments[i] += particles.accelerations[i] / 6.0;
+
+            particles.positions[i] = self.positions[i] + self.position_increments[i] * dt;
+            particles.velocities[i] = self.velocities[i] + self.velocity_increments[i] * dt;
+        }
+    }
+}
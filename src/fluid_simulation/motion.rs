@@ -0,0 +1,128 @@
+use std::f32::consts::TAU;
+
+use nalgebra::{Isometry2, Point2, Vector2};
+
+/// Prescribed rigid motion of an obstacle or the container.
+///
+/// Poses map the body's rest frame to the world frame at a given simulation time.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Motion {
+    #[default]
+    Static,
+    /// Constant velocity translation.
+    Translation { velocity: Vector2<f32> },
+    /// Constant angular velocity rotation about `pivot`, in radians per second.
+    Rotation {
+        pivot: Vector2<f32>,
+        angular_velocity: f32,
+    },
+    /// `amplitude * sin(2 pi frequency t + phase)` translation, e.g. a wave paddle or shaken tank.
+    Oscillation {
+        amplitude: Vector2<f32>,
+        frequency: f32,
+        phase: f32,
+    },
+    /// `amplitude * sin(2 pi frequency t + phase)` rotation about `pivot`, e.g. a pitching tank.
+    Pitching {
+        pivot: Vector2<f32>,
+        amplitude: f32,
+        frequency: f32,
+        phase: f32,
+    },
+}
+
+impl Motion {
+    pub fn state(&self, time: f32) -> KinematicState {
+        match *self {
+            Self::Static => KinematicState::default(),
+            Self::Translation { velocity } => KinematicState {
+                transform: Isometry2::translation(velocity.x * time, velocity.y * time),
+                center: velocity * time,
+                velocity,
+                angular_velocity: 0.0,
+            },
+            Self::Rotation {
+                pivot,
+                angular_velocity,
+            } => KinematicState::rotation_about(pivot, angular_velocity * time, angular_velocity),
+            Self::Oscillation {
+                amplitude,
+                frequency,
+                phase,
+            } => {
+                let angle = TAU * frequency * time + phase;
+                let offset = amplitude * angle.sin();
+                KinematicState {
+                    transform: Isometry2::translation(offset.x, offset.y),
+                    center: offset,
+                    velocity: amplitude * (TAU * frequency * angle.cos()),
+                    angular_velocity: 0.0,
+                }
+            }
+            Self::Pitching {
+                pivot,
+                amplitude,
+                frequency,
+                phase,
+            } => {
+                let angle = TAU * frequency * time + phase;
+                KinematicState::rotation_about(
+                    pivot,
+                    amplitude * angle.sin(),
+                    amplitude * TAU * frequency * angle.cos(),
+                )
+            }
+        }
+    }
+}
+
+/// Pose and rigid velocity field of a moving body at one instant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KinematicState {
+    pub transform: Isometry2<f32>,
+    /// World point whose velocity is `velocity`, the body rotates about it.
+    pub center: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+    pub angular_velocity: f32,
+}
+
+impl KinematicState {
+    fn rotation_about(pivot: Vector2<f32>, angle: f32, angular_velocity: f32) -> Self {
+        let rotation = Isometry2::rotation(angle);
+        Self {
+            transform: Isometry2::translation(pivot.x, pivot.y)
+                * rotation
+                * Isometry2::translation(-pivot.x, -pivot.y),
+            center: pivot,
+            velocity: Vector2::zeros(),
+            angular_velocity,
+        }
+    }
+
+    pub fn to_local(&self, point: Vector2<f32>) -> Vector2<f32> {
+        self.transform
+            .inverse_transform_point(&Point2::from(point))
+            .coords
+    }
+
+    pub fn to_world(&self, point: Vector2<f32>) -> Vector2<f32> {
+        self.transform.transform_point(&Point2::from(point)).coords
+    }
+
+    /// Velocity of the body at the world point `point`.
+    pub fn velocity_at(&self, point: Vector2<f32>) -> Vector2<f32> {
+        let offset = point - self.center;
+        self.velocity + Vector2::new(-offset.y, offset.x) * self.angular_velocity
+    }
+}
+
+impl Default for KinematicState {
+    fn default() -> Self {
+        Self {
+            transform: Isometry2::identity(),
+            center: Vector2::zeros(),
+            velocity: Vector2::zeros(),
+            angular_velocity: 0.0,
+        }
+    }
+}
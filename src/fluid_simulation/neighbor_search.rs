@@ -0,0 +1,337 @@
+use nalgebra::Vector2;
+
+use super::{BoundaryMode, Domain, Image};
+
+const HASH_PRIME_X: u32 = 73_856_093;
+const HASH_PRIME_Y: u32 = 19_349_663;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NeighborSearch {
+    #[default]
+    SpatialHash,
+    /// O(n^2) reference implementation, produces the same neighbor lists as `SpatialHash`.
+    BruteForce,
+}
+
+/// Per particle lists of all other particles closer than the search radius.
+///
+/// Lists are stored back to back and sorted by particle index, so sums over
+/// neighbors are evaluated in the same order regardless of the search method.
+///
+/// Every entry carries the `Image` of the neighbor the particle interacts
+/// with, the identity unless the domain has periodic or mirror axes. Across a
+/// mirror wall a particle may list itself or another particle twice.
+pub struct NeighborList {
+    search: NeighborSearch,
+    domain: Domain,
+
+    bucket_start: Vec<usize>,
+    bucket_entries: Vec<usize>,
+    candidates: Vec<(usize, Image)>,
+
+    offsets: Vec<usize>,
+    indices: Vec<usize>,
+    images: Vec<Image>,
+}
+
+impl NeighborList {
+    pub fn new(search: NeighborSearch) -> Self {
+        Self {
+            search,
+            domain: Domain::default(),
+            bucket_start: Vec::new(),
+            bucket_entries: Vec::new(),
+            candidates: Vec::new(),
+            offsets: vec![0],
+            indices: Vec::new(),
+            images: Vec::new(),
+        }
+    }
+
+    pub fn search(&self) -> NeighborSearch {
+        self.search
+    }
+
+    pub fn set_search(&mut self, search: NeighborSearch) {
+        self.search = search;
+    }
+
+    /// Domain whose periodic and mirror axes the next rebuild searches across.
+    pub fn set_domain(&mut self, domain: Domain) {
+        self.domain = domain;
+    }
+
+    pub fn neighbors(&self, i: usize) -> &[usize] {
+        &self.indices[self.offsets[i]..self.offsets[i + 1]]
+    }
+
+    pub fn images(&self, i: usize) -> &[Image] {
+        &self.images[self.offsets[i]..self.offsets[i + 1]]
+    }
+
+    /// Neighbor indices with their images.
+    pub fn with_images(&self, i: usize) -> impl Iterator<Item = (usize, Image)> + '_ {
+        self.neighbors(i)
+            .iter()
+            .copied()
+            .zip(self.images(i).iter().copied())
+    }
+
+    pub fn rebuild(&mut self, positions: &[Vector2<f32>], radius: f32) {
+        self.offsets.clear();
+        self.offsets.push(0);
+        self.indices.clear();
+        self.images.clear();
+
+        match self.search {
+            NeighborSearch::SpatialHash => self.rebuild_spatial_hash(positions, radius),
+            NeighborSearch::BruteForce => self.rebuild_brute_force(positions, radius),
+        }
+    }
+
+    /// Adds the images of `j` within `radius_sq` of particle `i` as candidates.
+    fn add_candidates(&mut self, positions: &[Vector2<f32>], i: usize, j: usize, radius_sq: f32) {
+        let position = positions[i];
+        let candidates = &mut self.candidates;
+        Image::candidates(&self.domain, position, positions[j], i != j, |image| {
+            if (image.position(positions[j]) - position).norm_squared() < radius_sq {
+                candidates.push((j, image));
+            }
+        });
+    }
+
+    /// Appends the candidates of a particle as its list, sorted by index.
+    fn push_candidates(&mut self) {
+        // Stable, images of one neighbor keep the order they were found in
+        self.candidates.sort_by_key(|&(j, _)| j);
+        for (j, image) in self.candidates.drain(..) {
+            self.indices.push(j);
+            self.images.push(image);
+        }
+        self.offsets.push(self.indices.len());
+    }
+
+    fn rebuild_brute_force(&mut self, positions: &[Vector2<f32>], radius: f32) {
+        let radius_sq = radius * radius;
+
+        for i in 0..positions.len() {
+            for j in 0..positions.len() {
+                self.add_candidates(positions, i, j, radius_sq);
+            }
+            self.push_candidates();
+        }
+    }
+
+    fn rebuild_spatial_hash(&mut self, positions: &[Vector2<f32>], radius: f32) {
+        let radius_sq = radius * radius;
+        let table_size = (2 * positions.len()).next_power_of_two();
+        let grid = Grid::new(&self.domain, radius);
+        let half_extents = self.domain.half_extents;
+
+        // Counting sort of particle indices by bucket, indices stay ascending within a bucket
+        self.bucket_start.clear();
+        self.bucket_start.resize(table_size + 1, 0);
+        for position in positions {
+            let bucket = Self::bucket(grid.cell(position), table_size);
+            self.bucket_start[bucket + 1] += 1;
+        }
+        for bucket in 0..table_size {
+            self.bucket_start[bucket + 1] += self.bucket_start[bucket];
+        }
+
+        self.bucket_entries.clear();
+        self.bucket_entries.resize(positions.len(), 0);
+        let mut fill = self.bucket_start[..table_size].to_vec();
+        for (i, position) in positions.iter().enumerate() {
+            let bucket = Self::bucket(grid.cell(position), table_size);
+            self.bucket_entries[fill[bucket]] = i;
+            fill[bucket] += 1;
+        }
+
+        let mut visited = Vec::with_capacity(36);
+        for (i, position) in positions.iter().enumerate() {
+            // A neighbor's mirror image is close to the particle exactly when the
+            // neighbor is close to the particle's own mirror image
+            let mut probes = vec![grid.fold(*position)];
+            for axis in 0..2 {
+                if self.domain.boundary_modes[axis] == BoundaryMode::Mirror
+                    && probes[0][axis].abs() > half_extents[axis] - radius
+                {
+                    let wall = half_extents[axis].copysign(probes[0][axis]);
+                    for k in 0..probes.len() {
+                        let mut probe = probes[k];
+                        probe[axis] = 2.0 * wall - probe[axis];
+                        probes.push(probe);
+                    }
+                }
+            }
+
+            // Distinct cells can share a bucket, visit each bucket once to avoid duplicates
+            visited.clear();
+            for probe in &probes {
+                let cell = grid.cell(probe);
+                for (dx, dy) in itertools::iproduct!(-1..=1, -1..=1) {
+                    let bucket = Self::bucket(grid.offset(cell, dx, dy), table_size);
+                    if visited.contains(&bucket) {
+                        continue;
+                    }
+                    visited.push(bucket);
+
+                    for entry in self.bucket_start[bucket]..self.bucket_start[bucket + 1] {
+                        let j = self.bucket_entries[entry];
+                        self.add_candidates(positions, i, j, radius_sq);
+                    }
+                }
+            }
+
+            self.push_candidates();
+        }
+    }
+
+    fn bucket((cx, cy): (i32, i32), table_size: usize) -> usize {
+        let hash = (cx as u32).wrapping_mul(HASH_PRIME_X) ^ (cy as u32).wrapping_mul(HASH_PRIME_Y);
+        hash as usize & (table_size - 1)
+    }
+}
+
+/// Cells of at least the search radius, periodic axes are divided into a
+/// whole number of cells that wrap around.
+///
+/// Positions outside a mirror wall are binned at their reflection, which is
+/// closer to every position inside.
+struct Grid {
+    cell_size: Vector2<f32>,
+    periods: [Option<i32>; 2],
+    mirrors: [bool; 2],
+    half_extents: Vector2<f32>,
+}
+
+impl Grid {
+    fn new(domain: &Domain, radius: f32) -> Self {
+        let mut cell_size = Vector2::repeat(radius);
+        let mut periods = [None; 2];
+        for axis in 0..2 {
+            if domain.boundary_modes[axis] == BoundaryMode::Periodic {
+                let period = domain.size()[axis];
+                let count = ((period / radius).floor() as i32).max(1);
+                cell_size[axis] = period / count as f32;
+                periods[axis] = Some(count);
+            }
+        }
+
+        Self {
+            cell_size,
+            periods,
+            mirrors: domain
+                .boundary_modes
+                .map(|mode| mode == BoundaryMode::Mirror),
+            half_extents: domain.half_extents,
+        }
+    }
+
+    fn fold(&self, mut position: Vector2<f32>) -> Vector2<f32> {
+        for axis in 0..2 {
+            let half_extent = self.half_extents[axis];
+            if self.mirrors[axis] && position[axis].abs() > half_extent {
+                let wall = half_extent.copysign(position[axis]);
+                position[axis] = (2.0 * wall - position[axis]).clamp(-half_extent, half_extent);
+            }
+        }
+        position
+    }
+
+    fn cell(&self, position: &Vector2<f32>) -> (i32, i32) {
+        let position = self.fold(*position);
+        let coordinate = |axis: usize| {
+            let cell = (position[axis] / self.cell_size[axis]).floor() as i32;
+            match self.periods[axis] {
+                Some(count) => cell.rem_euclid(count),
+                None => cell,
+            }
+        };
+
+        (coordinate(0), coordinate(1))
+    }
+
+    fn offset(&self, (cx, cy): (i32, i32), dx: i32, dy: i32) -> (i32, i32) {
+        let wrap = |axis: usize, cell: i32| match self.periods[axis] {
+            Some(count) => cell.rem_euclid(count),
+            None => cell,
+        };
+
+        (wrap(0, cx.wrapping_add(dx)), wrap(1, cy.wrapping_add(dy)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    /// Random positions in the domain grown by `margin`, some particles
+    /// have left through a wall before the step clamps them.
+    fn random_positions(domain: &Domain, margin: f32, count: usize) -> Vec<Vector2<f32>> {
+        let mut rng = StdRng::seed_from_u64(7);
+        let extents = domain.half_extents.add_scalar(margin);
+        (0..count)
+            .map(|_| {
+                Vector2::new(
+                    rng.gen_range(-extents.x..extents.x),
+                    rng.gen_range(-extents.y..extents.y),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn spatial_hash_matches_brute_force() {
+        use BoundaryMode::*;
+
+        let mode_pairs = [
+            [Wall, Wall],
+            [Periodic, Wall],
+            [Periodic, Periodic],
+            [Mirror, Mirror],
+            [Periodic, Mirror],
+            [Mirror, Periodic],
+        ];
+        let half_extents = [Vector2::new(1.0, 1.0), Vector2::new(1.5, 0.6)];
+
+        for boundary_modes in mode_pairs {
+            for half_extents in half_extents {
+                let domain = Domain {
+                    half_extents,
+                    boundary_modes,
+                };
+                let positions = random_positions(&domain, 0.01, 400);
+
+                for radius in [0.05, 0.2] {
+                    let mut hash = NeighborList::new(NeighborSearch::SpatialHash);
+                    let mut brute_force = NeighborList::new(NeighborSearch::BruteForce);
+                    hash.set_domain(domain);
+                    brute_force.set_domain(domain);
+                    hash.rebuild(&positions, radius);
+                    brute_force.rebuild(&positions, radius);
+
+                    for i in 0..positions.len() {
+                        assert_eq!(
+                            hash.neighbors(i),
+                            brute_force.neighbors(i),
+                            "{domain:?} radius {radius} particle {i}"
+                        );
+                        assert_eq!(
+                            hash.images(i),
+                            brute_force.images(i),
+                            "{domain:?} radius {radius} particle {i}"
+                        );
+
+                        for (j, image) in hash.with_images(i) {
+                            assert!((image.position(positions[j]) - positions[i]).norm() < radius);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
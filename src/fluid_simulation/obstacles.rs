@@ -0,0 +1,318 @@
+use nalgebra::Vector2;
+
+use super::{
+    params::{in_range, non_negative},
+    KinematicState, Motion, ParamsError,
+};
+
+/// Shape described by its signed distance, negative inside.
+///
+/// Implemented for closures `Fn(Vector2<f32>) -> f32`, so any analytic
+/// distance function can be used directly.
+pub trait SignedDistance {
+    fn signed_distance(&self, point: Vector2<f32>) -> f32;
+
+    /// Outward unit normal, central differences of the distance by default.
+    fn normal(&self, point: Vector2<f32>) -> Vector2<f32> {
+        const EPSILON: f32 = 1e-4;
+        let dx = Vector2::new(EPSILON, 0.0);
+        let dy = Vector2::new(0.0, EPSILON);
+
+        let gradient = Vector2::new(
+            self.signed_distance(point + dx) - self.signed_distance(point - dx),
+            self.signed_distance(point + dy) - self.signed_distance(point - dy),
+        );
+        gradient.try_normalize(f32::EPSILON).unwrap_or_default()
+    }
+}
+
+impl<F: Fn(Vector2<f32>) -> f32> SignedDistance for F {
+    fn signed_distance(&self, point: Vector2<f32>) -> f32 {
+        self(point)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub center: Vector2<f32>,
+    pub radius: f32,
+}
+
+impl SignedDistance for Circle {
+    fn signed_distance(&self, point: Vector2<f32>) -> f32 {
+        (point - self.center).norm() - self.radius
+    }
+
+    fn normal(&self, point: Vector2<f32>) -> Vector2<f32> {
+        (point - self.center)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector2::y)
+    }
+}
+
+/// Segment from `start` to `end` thickened by `radius`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capsule {
+    pub start: Vector2<f32>,
+    pub end: Vector2<f32>,
+    pub radius: f32,
+}
+
+impl Capsule {
+    fn closest_point(&self, point: Vector2<f32>) -> Vector2<f32> {
+        let segment = self.end - self.start;
+        let length_sq = segment.norm_squared();
+        if length_sq <= 0.0 {
+            return self.start;
+        }
+
+        let t = ((point - self.start).dot(&segment) / length_sq).clamp(0.0, 1.0);
+        self.start + segment * t
+    }
+}
+
+impl SignedDistance for Capsule {
+    fn signed_distance(&self, point: Vector2<f32>) -> f32 {
+        (point - self.closest_point(point)).norm() - self.radius
+    }
+
+    fn normal(&self, point: Vector2<f32>) -> Vector2<f32> {
+        (point - self.closest_point(point))
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector2::y)
+    }
+}
+
+/// Simple polygon, convex or concave, with vertices in either winding order.
+///
+/// Always has at least 3 finite vertices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    pub(super) vertices: Vec<Vector2<f32>>,
+}
+
+impl Polygon {
+    pub fn new(vertices: Vec<Vector2<f32>>) -> Result<Self, ParamsError> {
+        if vertices.len() < 3 {
+            return Err(ParamsError::TooFewVertices(vertices.len()));
+        }
+        if !vertices.iter().flatten().all(|x| x.is_finite()) {
+            return Err(ParamsError::NotFinite("polygon vertex"));
+        }
+
+        Ok(Self { vertices })
+    }
+
+    /// Axis aligned rectangle given by its center and half extents.
+    pub fn rectangle(center: Vector2<f32>, half_extents: Vector2<f32>) -> Self {
+        Self {
+            vertices: vec![
+                center + Vector2::new(-half_extents.x, -half_extents.y),
+                center + Vector2::new(half_extents.x, -half_extents.y),
+                center + Vector2::new(half_extents.x, half_extents.y),
+                center + Vector2::new(-half_extents.x, half_extents.y),
+            ],
+        }
+    }
+
+    pub fn vertices(&self) -> &[Vector2<f32>] {
+        &self.vertices
+    }
+}
+
+impl SignedDistance for Polygon {
+    // Distance to the closest edge, sign from the even-odd rule
+    fn signed_distance(&self, point: Vector2<f32>) -> f32 {
+        let mut distance_sq = f32::INFINITY;
+        let mut inside = false;
+
+        let mut previous = self.vertices[self.vertices.len() - 1];
+        for &vertex in &self.vertices {
+            let edge = previous - vertex;
+            let offset = point - vertex;
+            let t = (offset.dot(&edge) / edge.norm_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+            distance_sq = distance_sq.min((offset - edge * t).norm_squared());
+
+            if (vertex.y > point.y) != (previous.y > point.y)
+                && point.x
+                    < vertex.x
+                        + (point.y - vertex.y) * (previous.x - vertex.x) / (previous.y - vertex.y)
+            {
+                inside = !inside;
+            }
+
+            previous = vertex;
+        }
+
+        if inside {
+            -distance_sq.sqrt()
+        } else {
+            distance_sq.sqrt()
+        }
+    }
+}
+
+/// Collider following a prescribed motion, particles inside are pushed out
+/// along the surface normal.
+///
+/// The shape is given in the obstacle's rest frame. Collisions act on the
+/// particle velocity relative to the obstacle surface, so a moving obstacle
+/// drags colliding particles along.
+///
+/// `restitution` is the fraction of the normal velocity kept after impact,
+/// `friction` the Coulomb coefficient that removes tangential velocity in
+/// proportion to the normal impulse.
+///
+/// An obstacle with a `temperature` heats or cools nearby particles, see `Heat`.
+pub struct Obstacle {
+    pub(super) shape: Box<dyn SignedDistance>,
+    pub(super) restitution: f32,
+    pub(super) friction: f32,
+    pub(super) motion: Motion,
+    pub(super) temperature: Option<f32>,
+}
+
+impl Obstacle {
+    pub fn new(
+        shape: impl SignedDistance + 'static,
+        restitution: f32,
+        friction: f32,
+    ) -> Result<Self, ParamsError> {
+        in_range("restitution", restitution, 0.0, 1.0)?;
+        non_negative("friction", friction)?;
+
+        Ok(Self {
+            shape: Box::new(shape),
+            restitution,
+            friction,
+            motion: Motion::Static,
+            temperature: None,
+        })
+    }
+
+    pub fn with_motion(mut self, motion: Motion) -> Self {
+        self.motion = motion;
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn shape(&self) -> &dyn SignedDistance {
+        self.shape.as_ref()
+    }
+
+    pub fn restitution(&self) -> f32 {
+        self.restitution
+    }
+
+    pub fn friction(&self) -> f32 {
+        self.friction
+    }
+
+    pub fn motion(&self) -> Motion {
+        self.motion
+    }
+
+    pub fn temperature(&self) -> Option<f32> {
+        self.temperature
+    }
+
+    /// Moves a particle closer than `margin` to the surface back out and
+    /// reflects its velocity relative to the surface if it is moving inwards.
+    pub(super) fn collide(
+        &self,
+        state: &KinematicState,
+        margin: f32,
+        position: &mut Vector2<f32>,
+        velocity: &mut Vector2<f32>,
+    ) {
+        collide_with_surface(
+            self.shape.as_ref(),
+            state,
+            self.restitution,
+            self.friction,
+            margin,
+            position,
+            velocity,
+        );
+    }
+}
+
+/// Particle response shared by obstacles and rigid bodies, see [`Obstacle`].
+pub(super) fn collide_with_surface(
+    shape: &dyn SignedDistance,
+    state: &KinematicState,
+    restitution: f32,
+    friction: f32,
+    margin: f32,
+    position: &mut Vector2<f32>,
+    velocity: &mut Vector2<f32>,
+) {
+    let local = state.to_local(*position);
+    let distance = shape.signed_distance(local);
+    if distance >= margin {
+        return;
+    }
+
+    let normal = state.transform.rotation * shape.normal(local);
+    *position += normal * (margin - distance);
+
+    let surface_velocity = state.velocity_at(*position);
+    let relative_velocity = *velocity - surface_velocity;
+    let normal_velocity = relative_velocity.dot(&normal);
+    if normal_velocity >= 0.0 {
+        return;
+    }
+
+    let tangential = relative_velocity - normal * normal_velocity;
+    let tangential_norm = tangential.norm();
+    let friction_scale = if tangential_norm > 0.0 {
+        (1.0 - friction * (1.0 + restitution) * -normal_velocity / tangential_norm).max(0.0)
+    } else {
+        0.0
+    };
+
+    *velocity =
+        surface_velocity + tangential * friction_scale - normal * (restitution * normal_velocity);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_obstacles_are_rejected() {
+        assert_eq!(
+            Polygon::new(Vec::new()),
+            Err(ParamsError::TooFewVertices(0))
+        );
+        assert_eq!(
+            Polygon::new(vec![
+                Vector2::zeros(),
+                Vector2::x(),
+                Vector2::new(f32::NAN, 1.0)
+            ]),
+            Err(ParamsError::NotFinite("polygon vertex"))
+        );
+
+        let circle = Circle {
+            center: Vector2::zeros(),
+            radius: 0.1,
+        };
+        assert!(matches!(
+            Obstacle::new(circle, 1.5, 0.0),
+            Err(ParamsError::OutOfRange {
+                name: "restitution",
+                ..
+            })
+        ));
+        assert!(matches!(
+            Obstacle::new(circle, 0.5, -0.1),
+            Err(ParamsError::Negative("friction", _))
+        ));
+        assert!(Obstacle::new(circle, 0.5, 0.1).is_ok());
+    }
+}
@@ -0,0 +1,561 @@
+use std::fmt;
+
+use nalgebra::Vector2;
+
+use super::{
+    ArtificialViscosity, BoundaryMode, Domain, EquationOfState, Heat, Kernel, Kernels, Phase,
+    SmoothingKernel, SurfaceTension, TimeStepping, ViscosityModel,
+};
+
+/// Physical and time stepping parameters of a `FluidSimulation`.
+///
+/// Only constructed through `SimulationParamsBuilder::build`, so every instance is valid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationParams {
+    pub(super) smoothing_radius: f32,
+    pub(super) kernels: Kernels,
+    pub(super) mass: f32,
+    pub(super) rest_density: f32,
+    pub(super) equation_of_state: EquationOfState,
+    pub(super) clamp_negative_pressure: bool,
+    pub(super) viscosity: f32,
+    pub(super) viscosity_model: ViscosityModel,
+    pub(super) phases: Vec<Phase>,
+    pub(super) artificial_viscosity: Option<ArtificialViscosity>,
+    pub(super) xsph: Option<f32>,
+    pub(super) surface_tension: Option<SurfaceTension>,
+    pub(super) heat: Option<Heat>,
+    pub(super) gravity: Vector2<f32>,
+    pub(super) bound_damping: f32,
+    pub(super) domain_size: Vector2<f32>,
+    pub(super) boundary_modes: [BoundaryMode; 2],
+    pub(super) time_stepping: TimeStepping,
+}
+
+impl SimulationParams {
+    pub fn builder() -> SimulationParamsBuilder {
+        SimulationParamsBuilder {
+            params: Self::default(),
+        }
+    }
+
+    pub fn to_builder(&self) -> SimulationParamsBuilder {
+        SimulationParamsBuilder {
+            params: self.clone(),
+        }
+    }
+
+    pub fn smoothing_radius(&self) -> f32 {
+        self.smoothing_radius
+    }
+
+    pub fn kernels(&self) -> Kernels {
+        self.kernels
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.mass
+    }
+
+    pub fn rest_density(&self) -> f32 {
+        self.rest_density
+    }
+
+    pub fn equation_of_state(&self) -> EquationOfState {
+        self.equation_of_state
+    }
+
+    pub fn clamp_negative_pressure(&self) -> bool {
+        self.clamp_negative_pressure
+    }
+
+    pub fn pressure(&self, density: f32) -> f32 {
+        self.phase_pressure(density, self.rest_density)
+    }
+
+    /// Pressure of a phase with the given rest density.
+    pub fn phase_pressure(&self, density: f32, rest_density: f32) -> f32 {
+        let pressure = self.equation_of_state.pressure(density, rest_density);
+
+        if self.clamp_negative_pressure {
+            pressure.max(0.0)
+        } else {
+            pressure
+        }
+    }
+
+    pub fn viscosity(&self) -> f32 {
+        self.viscosity
+    }
+
+    pub fn viscosity_model(&self) -> ViscosityModel {
+        self.viscosity_model
+    }
+
+    /// Phases besides the base fluid, phase `k` is `phases()[k - 1]`.
+    pub fn phases(&self) -> &[Phase] {
+        &self.phases
+    }
+
+    pub fn num_phases(&self) -> usize {
+        self.phases.len() + 1
+    }
+
+    pub(super) fn check_phase(&self, phase: u32) -> Result<(), ParamsError> {
+        if (phase as usize) < self.num_phases() {
+            Ok(())
+        } else {
+            Err(ParamsError::UnknownPhase {
+                phase,
+                num_phases: self.num_phases(),
+            })
+        }
+    }
+
+    /// Properties of phase `id`, unknown ids fall back to the base fluid.
+    pub fn phase(&self, id: u32) -> Phase {
+        (id as usize)
+            .checked_sub(1)
+            .and_then(|k| self.phases.get(k).copied())
+            .unwrap_or(Phase {
+                rest_density: self.rest_density,
+                mass: self.mass,
+                viscosity: self.viscosity,
+                color: Phase::BASE_COLOR,
+            })
+    }
+
+    /// The same parameters with every particle treated as the base fluid.
+    pub(super) fn single_phase(&self) -> Self {
+        Self {
+            phases: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    pub fn artificial_viscosity(&self) -> Option<ArtificialViscosity> {
+        self.artificial_viscosity
+    }
+
+    /// XSPH velocity smoothing factor, applied after every solver step.
+    pub fn xsph(&self) -> Option<f32> {
+        self.xsph
+    }
+
+    pub fn surface_tension(&self) -> Option<SurfaceTension> {
+        self.surface_tension
+    }
+
+    pub fn heat(&self) -> Option<Heat> {
+        self.heat
+    }
+
+    pub fn gravity(&self) -> Vector2<f32> {
+        self.gravity
+    }
+
+    pub fn bound_damping(&self) -> f32 {
+        self.bound_damping
+    }
+
+    /// Width and height of the domain centered at the origin, in world units.
+    pub fn domain_size(&self) -> Vector2<f32> {
+        self.domain_size
+    }
+
+    /// Treatment of the x and y axes of the domain.
+    pub fn boundary_modes(&self) -> [BoundaryMode; 2] {
+        self.boundary_modes
+    }
+
+    pub fn domain(&self) -> Domain {
+        Domain {
+            half_extents: 0.5 * self.domain_size,
+            boundary_modes: self.boundary_modes,
+        }
+    }
+
+    pub fn time_stepping(&self) -> &TimeStepping {
+        &self.time_stepping
+    }
+
+    fn validate(&self) -> Result<(), ParamsError> {
+        positive("smoothing radius", self.smoothing_radius)?;
+        if !self.kernels.density.is_bounded() {
+            return Err(ParamsError::UnboundedDensityKernel(self.kernels.density));
+        }
+        positive("mass", self.mass)?;
+        positive("rest density", self.rest_density)?;
+        match self.equation_of_state {
+            EquationOfState::Linear { stiffness } => non_negative("EOS stiffness", stiffness)?,
+            EquationOfState::Tait {
+                speed_of_sound,
+                exponent,
+            } => {
+                positive("speed of sound", speed_of_sound)?;
+                in_range("Tait exponent", exponent, 1.0, f32::MAX)?;
+            }
+        }
+        non_negative("viscosity", self.viscosity)?;
+        match self.viscosity_model {
+            ViscosityModel::Newtonian => {}
+            ViscosityModel::PowerLaw {
+                consistency,
+                index,
+                max_viscosity,
+            } => {
+                non_negative("power law consistency", consistency)?;
+                positive("power law index", index)?;
+                non_negative("max viscosity", max_viscosity)?;
+            }
+            ViscosityModel::Carreau {
+                zero_shear_viscosity,
+                infinite_shear_viscosity,
+                relaxation_time,
+                index,
+            }
+            | ViscosityModel::Cross {
+                zero_shear_viscosity,
+                infinite_shear_viscosity,
+                time_constant: relaxation_time,
+                index,
+            } => {
+                non_negative("zero shear viscosity", zero_shear_viscosity)?;
+                non_negative("infinite shear viscosity", infinite_shear_viscosity)?;
+                non_negative("viscosity time constant", relaxation_time)?;
+                non_negative("viscosity model index", index)?;
+            }
+            ViscosityModel::Bingham {
+                yield_stress,
+                plastic_viscosity,
+                regularization,
+            } => {
+                non_negative("yield stress", yield_stress)?;
+                non_negative("plastic viscosity", plastic_viscosity)?;
+                positive("Bingham regularization", regularization)?;
+            }
+        }
+        for phase in &self.phases {
+            positive("phase mass", phase.mass)?;
+            positive("phase rest density", phase.rest_density)?;
+            non_negative("phase viscosity", phase.viscosity)?;
+        }
+        if let Some(artificial_viscosity) = &self.artificial_viscosity {
+            non_negative("artificial viscosity alpha", artificial_viscosity.alpha)?;
+            non_negative("artificial viscosity beta", artificial_viscosity.beta)?;
+        }
+        if let Some(xsph) = self.xsph {
+            in_range("XSPH factor", xsph, 0.0, 1.0)?;
+        }
+        match self.surface_tension {
+            Some(SurfaceTension::Akinci { coefficient }) => {
+                non_negative("surface tension", coefficient)?
+            }
+            Some(SurfaceTension::ColorField {
+                coefficient,
+                threshold,
+            }) => {
+                non_negative("surface tension", coefficient)?;
+                non_negative("color field threshold", threshold)?;
+            }
+            None => {}
+        }
+        if let Some(heat) = &self.heat {
+            non_negative("thermal diffusivity", heat.diffusivity)?;
+            if !heat.expansion.is_finite() {
+                return Err(ParamsError::NotFinite("thermal expansion"));
+            }
+            non_negative("surface heat transfer", heat.surface_transfer)?;
+        }
+        in_range("bound damping", self.bound_damping, -1.0, 0.0)?;
+        if !self.gravity.iter().all(|g| g.is_finite()) {
+            return Err(ParamsError::NotFinite("gravity"));
+        }
+
+        positive("domain width", self.domain_size.x)?;
+        positive("domain height", self.domain_size.y)?;
+        if 2.0 * self.smoothing_radius >= self.domain_size.min() {
+            return Err(ParamsError::SmoothingRadiusExceedsDomain {
+                smoothing_radius: self.smoothing_radius,
+                domain_size: self.domain_size,
+            });
+        }
+
+        let time_stepping = &self.time_stepping;
+        positive("time step", time_stepping.step)?;
+        at_least_one("substeps", time_stepping.substeps)?;
+        at_least_one("max steps per update", time_stepping.max_steps_per_update)?;
+        non_negative("time scale", time_stepping.time_scale)?;
+
+        if let Some(adaptive) = &time_stepping.adaptive {
+            positive("CFL factor", adaptive.cfl_factor)?;
+            positive("force factor", adaptive.force_factor)?;
+            positive("viscosity factor", adaptive.viscosity_factor)?;
+            positive("min step", adaptive.min_step)?;
+
+            if adaptive.min_step > time_stepping.substep() {
+                return Err(ParamsError::MinStepExceedsSubstep {
+                    min_step: adaptive.min_step,
+                    substep: time_stepping.substep(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for SimulationParams {
+    fn default() -> Self {
+        Self {
+            smoothing_radius: 0.04,
+            kernels: Kernels::default(),
+            mass: 0.001,
+            rest_density: 1.0,
+            equation_of_state: EquationOfState::default(),
+            clamp_negative_pressure: false,
+            viscosity: 0.001,
+            viscosity_model: ViscosityModel::Newtonian,
+            phases: Vec::new(),
+            artificial_viscosity: None,
+            xsph: None,
+            surface_tension: None,
+            heat: None,
+            gravity: Vector2::new(0.0, -1.0),
+            bound_damping: -0.5,
+            domain_size: Vector2::new(2.0, 2.0),
+            boundary_modes: [BoundaryMode::Wall; 2],
+            time_stepping: TimeStepping::default(),
+        }
+    }
+}
+
+pub struct SimulationParamsBuilder {
+    params: SimulationParams,
+}
+
+impl SimulationParamsBuilder {
+    pub fn smoothing_radius(mut self, smoothing_radius: f32) -> Self {
+        self.params.smoothing_radius = smoothing_radius;
+        self
+    }
+
+    pub fn kernels(mut self, kernels: Kernels) -> Self {
+        self.params.kernels = kernels;
+        self
+    }
+
+    pub fn mass(mut self, mass: f32) -> Self {
+        self.params.mass = mass;
+        self
+    }
+
+    pub fn rest_density(mut self, rest_density: f32) -> Self {
+        self.params.rest_density = rest_density;
+        self
+    }
+
+    pub fn equation_of_state(mut self, equation_of_state: EquationOfState) -> Self {
+        self.params.equation_of_state = equation_of_state;
+        self
+    }
+
+    pub fn clamp_negative_pressure(mut self, clamp_negative_pressure: bool) -> Self {
+        self.params.clamp_negative_pressure = clamp_negative_pressure;
+        self
+    }
+
+    pub fn viscosity(mut self, viscosity: f32) -> Self {
+        self.params.viscosity = viscosity;
+        self
+    }
+
+    /// Shear rate dependence of the viscosity, `Newtonian` keeps the viscosity of each phase.
+    pub fn viscosity_model(mut self, viscosity_model: ViscosityModel) -> Self {
+        self.params.viscosity_model = viscosity_model;
+        self
+    }
+
+    /// Adds a phase, numbered from 1 in the order of the calls.
+    pub fn phase(mut self, phase: Phase) -> Self {
+        self.params.phases.push(phase);
+        self
+    }
+
+    pub fn phases(mut self, phases: Vec<Phase>) -> Self {
+        self.params.phases = phases;
+        self
+    }
+
+    pub fn artificial_viscosity(
+        mut self,
+        artificial_viscosity: Option<ArtificialViscosity>,
+    ) -> Self {
+        self.params.artificial_viscosity = artificial_viscosity;
+        self
+    }
+
+    pub fn xsph(mut self, xsph: Option<f32>) -> Self {
+        self.params.xsph = xsph;
+        self
+    }
+
+    pub fn surface_tension(mut self, surface_tension: Option<SurfaceTension>) -> Self {
+        self.params.surface_tension = surface_tension;
+        self
+    }
+
+    pub fn heat(mut self, heat: Option<Heat>) -> Self {
+        self.params.heat = heat;
+        self
+    }
+
+    pub fn gravity(mut self, gravity: Vector2<f32>) -> Self {
+        self.params.gravity = gravity;
+        self
+    }
+
+    pub fn bound_damping(mut self, bound_damping: f32) -> Self {
+        self.params.bound_damping = bound_damping;
+        self
+    }
+
+    pub fn domain_size(mut self, domain_size: Vector2<f32>) -> Self {
+        self.params.domain_size = domain_size;
+        self
+    }
+
+    pub fn boundary_modes(mut self, boundary_modes: [BoundaryMode; 2]) -> Self {
+        self.params.boundary_modes = boundary_modes;
+        self
+    }
+
+    pub fn time_stepping(mut self, time_stepping: TimeStepping) -> Self {
+        self.params.time_stepping = time_stepping;
+        self
+    }
+
+    pub fn build(self) -> Result<SimulationParams, ParamsError> {
+        self.params.validate()?;
+        Ok(self.params)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamsError {
+    NotFinite(&'static str),
+    NotPositive(&'static str, f32),
+    Negative(&'static str, f32),
+    Zero(&'static str),
+    OutOfRange {
+        name: &'static str,
+        value: f32,
+        min: f32,
+        max: f32,
+    },
+    SmoothingRadiusExceedsDomain {
+        smoothing_radius: f32,
+        domain_size: Vector2<f32>,
+    },
+    UnboundedDensityKernel(SmoothingKernel),
+    MinStepExceedsSubstep {
+        min_step: f32,
+        substep: f32,
+    },
+    TooFewVertices(usize),
+    UnknownPhase {
+        phase: u32,
+        num_phases: usize,
+    },
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFinite(name) => write!(f, "{name} must be finite"),
+            Self::NotPositive(name, value) => write!(f, "{name} must be positive, got {value}"),
+            Self::Negative(name, value) => write!(f, "{name} must not be negative, got {value}"),
+            Self::Zero(name) => write!(f, "{name} must be at least 1"),
+            Self::OutOfRange {
+                name,
+                value,
+                min,
+                max,
+            } => write!(f, "{name} must be in [{min}, {max}], got {value}"),
+            Self::SmoothingRadiusExceedsDomain {
+                smoothing_radius,
+                domain_size,
+            } => write!(
+                f,
+                "smoothing radius {smoothing_radius} leaves no room for particles in the {} x {} domain",
+                domain_size.x, domain_size.y
+            ),
+            Self::UnboundedDensityKernel(kernel) => write!(
+                f,
+                "{} kernel is singular at r = 0 and can't be used for densities",
+                kernel.name()
+            ),
+            Self::MinStepExceedsSubstep { min_step, substep } => write!(
+                f,
+                "adaptive min step {min_step} is larger than the maximum substep {substep}"
+            ),
+            Self::TooFewVertices(count) => {
+                write!(f, "a polygon needs at least 3 vertices, got {count}")
+            }
+            Self::UnknownPhase { phase, num_phases } => write!(
+                f,
+                "phase {phase} is not defined, the simulation params have {num_phases} phases"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+pub(super) fn positive(name: &'static str, value: f32) -> Result<(), ParamsError> {
+    if !value.is_finite() {
+        Err(ParamsError::NotFinite(name))
+    } else if value <= 0.0 {
+        Err(ParamsError::NotPositive(name, value))
+    } else {
+        Ok(())
+    }
+}
+
+pub(super) fn non_negative(name: &'static str, value: f32) -> Result<(), ParamsError> {
+    if !value.is_finite() {
+        Err(ParamsError::NotFinite(name))
+    } else if value < 0.0 {
+        Err(ParamsError::Negative(name, value))
+    } else {
+        Ok(())
+    }
+}
+
+pub(super) fn in_range(
+    name: &'static str,
+    value: f32,
+    min: f32,
+    max: f32,
+) -> Result<(), ParamsError> {
+    if !value.is_finite() {
+        Err(ParamsError::NotFinite(name))
+    } else if value < min || value > max {
+        Err(ParamsError::OutOfRange {
+            name,
+            value,
+            min,
+            max,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+pub(super) fn at_least_one(name: &'static str, value: u32) -> Result<(), ParamsError> {
+    if value == 0 {
+        Err(ParamsError::Zero(name))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use nalgebra::Vector2;
+
+pub struct Particles {
+    pub positions: Vec<Vector2<f32>>,
+    pub velocities: Vec<Vector2<f32>>,
+    pub accelerations: Vec<Vector2<f32>>,
+    pub densities: Vec<f32>,
+    pub pressures: Vec<f32>,
+    /// Effective viscosities of the last evaluated state, see `ViscosityModel`.
+    pub viscosities: Vec<f32>,
+    /// Phase ids, see `SimulationParams::phase`.
+    pub phases: Vec<u32>,
+    /// Offsets from the ambient temperature, see `Heat`.
+    pub temperatures: Vec<f32>,
+}
+
+impl Particles {
+    pub fn new(positions: Vec<Vector2<f32>>, velocities: Vec<Vector2<f32>>) -> Self {
+        assert_eq!(positions.len(), velocities.len());
+        let num_particles = positions.len();
+
+        Self {
+            positions,
+            velocities,
+            accelerations: vec![Vector2::zeros(); num_particles],
+            densities: vec![0.0; num_particles],
+            pressures: vec![0.0; num_particles],
+            viscosities: vec![0.0; num_particles],
+            phases: vec![0; num_particles],
+            temperatures: vec![0.0; num_particles],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn push(
+        &mut self,
+        position: Vector2<f32>,
+        velocity: Vector2<f32>,
+        phase: u32,
+        temperature: f32,
+    ) {
+        self.positions.push(position);
+        self.velocities.push(velocity);
+        self.accelerations.push(Vector2::zeros());
+        self.densities.push(0.0);
+        self.pressures.push(0.0);
+        self.viscosities.push(0.0);
+        self.phases.push(phase);
+        self.temperatures.push(temperature);
+    }
+
+    /// Removes the particles for which `keep` returns false, preserving the order of the rest.
+    pub fn retain(&mut self, mut keep: impl FnMut(Vector2<f32>) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len() {
+            if !keep(self.positions[i]) {
+                continue;
+            }
+
+            self.positions[kept] = self.positions[i];
+            self.velocities[kept] = self.velocities[i];
+            self.accelerations[kept] = self.accelerations[i];
+            self.densities[kept] = self.densities[i];
+            self.pressures[kept] = self.pressures[i];
+            self.viscosities[kept] = self.viscosities[i];
+            self.phases[kept] = self.phases[i];
+            self.temperatures[kept] = self.temperatures[i];
+            kept += 1;
+        }
+
+        self.positions.truncate(kept);
+        self.velocities.truncate(kept);
+        self.accelerations.truncate(kept);
+        self.densities.truncate(kept);
+        self.pressures.truncate(kept);
+        self.viscosities.truncate(kept);
+        self.phases.truncate(kept);
+        self.temperatures.truncate(kept);
+    }
+}
@@ -0,0 +1,302 @@
+use nalgebra::Vector2;
+
+use super::{
+    artificial_viscosity, heat,
+    params::{at_least_one, in_range, non_negative, positive},
+    sph, surface_tension, Kernel, ParamsError, Solver, SolverContext, SolverStats,
+};
+
+/// Position Based Fluids (Macklin & Müller 2013).
+///
+/// Density constraints `C_i = rho_i / rho_0 - 1` are projected with Jacobi
+/// iterations, the artificial pressure term `s_corr` keeps particles from
+/// clustering at the free surface and XSPH smooths the resulting velocities.
+pub struct Pbf {
+    iterations: u32,
+    relaxation: f32,
+    tensile_strength: f32,
+    tensile_distance: f32,
+    tensile_exponent: u32,
+    xsph_viscosity: f32,
+
+    external_accelerations: Vec<Vector2<f32>>,
+    previous_positions: Vec<Vector2<f32>>,
+    previous_velocities: Vec<Vector2<f32>>,
+    lambdas: Vec<f32>,
+    denominators: Vec<f32>,
+    corrections: Vec<Vector2<f32>>,
+    velocity_corrections: Vec<Vector2<f32>>,
+}
+
+impl Pbf {
+    pub fn new(iterations: u32) -> Result<Self, ParamsError> {
+        at_least_one("PBF iterations", iterations)?;
+
+        Ok(Self {
+            iterations,
+            ..Self::default()
+        })
+    }
+
+    /// Constraint force mixing term added to the `lambda` denominator.
+    pub fn with_relaxation(mut self, relaxation: f32) -> Result<Self, ParamsError> {
+        positive("PBF relaxation", relaxation)?;
+        self.relaxation = relaxation;
+        Ok(self)
+    }
+
+    /// `k`, `delta_q / h` and `n` of `s_corr = -k * (W(r) / W(delta_q))^n`.
+    ///
+    /// `s_corr` is applied like an additional constraint violation, i.e. scaled
+    /// by the same denominator as `lambda`, which keeps `k` independent of units.
+    /// `delta_q / h` is typically 0.1 to 0.3 and may be at most 0.5.
+    pub fn with_tensile_correction(
+        mut self,
+        strength: f32,
+        distance: f32,
+        exponent: u32,
+    ) -> Result<Self, ParamsError> {
+        non_negative("PBF tensile strength", strength)?;
+        in_range("PBF tensile distance", distance, 0.0, 0.5)?;
+        at_least_one("PBF tensile exponent", exponent)?;
+        self.tensile_strength = strength;
+        self.tensile_distance = distance;
+        self.tensile_exponent = exponent;
+        Ok(self)
+    }
+
+    /// Built-in XSPH factor, applied on top of `SimulationParams::xsph`.
+    pub fn with_xsph_viscosity(mut self, xsph_viscosity: f32) -> Result<Self, ParamsError> {
+        in_range("PBF XSPH factor", xsph_viscosity, 0.0, 1.0)?;
+        self.xsph_viscosity = xsph_viscosity;
+        Ok(self)
+    }
+
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    pub fn relaxation(&self) -> f32 {
+        self.relaxation
+    }
+
+    pub fn tensile_strength(&self) -> f32 {
+        self.tensile_strength
+    }
+
+    pub fn tensile_distance(&self) -> f32 {
+        self.tensile_distance
+    }
+
+    pub fn tensile_exponent(&self) -> u32 {
+        self.tensile_exponent
+    }
+
+    pub fn xsph_viscosity(&self) -> f32 {
+        self.xsph_viscosity
+    }
+}
+
+impl Default for Pbf {
+    fn default() -> Self {
+        Self {
+            iterations: 4,
+            relaxation: 1.0,
+            tensile_strength: 0.1,
+            tensile_distance: 0.2,
+            tensile_exponent: 4,
+            xsph_viscosity: 0.01,
+            external_accelerations: Vec::new(),
+            previous_positions: Vec::new(),
+            previous_velocities: Vec::new(),
+            lambdas: Vec::new(),
+            denominators: Vec::new(),
+            corrections: Vec::new(),
+            velocity_corrections: Vec::new(),
+        }
+    }
+}
+
+impl Solver for Pbf {
+    fn name(&self) -> &'static str {
+        "PBF"
+    }
+
+    fn step(&mut self, context: &mut SolverContext, dt: f32) -> SolverStats {
+        let params = context.params;
+        let particles = &mut *context.particles;
+        let neighbors = &mut *context.neighbors;
+        let container = context.container;
+        let num_particles = particles.len();
+        let smoothing_radius = params.smoothing_radius;
+        let scale = params.mass / params.rest_density;
+        let kernels = params.kernels;
+        let tensile_reference = kernels
+            .density
+            .value(self.tensile_distance * smoothing_radius, smoothing_radius);
+
+        self.previous_positions.clone_from(&particles.positions);
+        self.previous_velocities.clone_from(&particles.velocities);
+        self.lambdas.resize(num_particles, 0.0);
+        self.denominators.resize(num_particles, 0.0);
+        self.corrections.resize(num_particles, Vector2::zeros());
+        particles.pressures.fill(0.0);
+
+        self.external_accelerations.clear();
+        self.external_accelerations.extend(
+            particles
+                .temperatures
+                .iter()
+                .map(|&temperature| heat::buoyant_gravity(params, temperature)),
+        );
+        if params.surface_tension.is_some() || params.artificial_viscosity.is_some() {
+            neighbors.rebuild(&particles.positions, smoothing_radius);
+            sph::compute_densities(
+                &particles.positions,
+                &particles.phases,
+                neighbors,
+                None,
+                params,
+                &mut particles.densities,
+            );
+            artificial_viscosity::add_artificial_viscosity_accelerations(
+                particles,
+                neighbors,
+                params,
+                &mut self.external_accelerations,
+            );
+            surface_tension::add_surface_tension_accelerations(
+                particles,
+                neighbors,
+                params,
+                &mut self.external_accelerations,
+            );
+        }
+
+        for i in 0..num_particles {
+            particles.velocities[i] += self.external_accelerations[i] * dt;
+            particles.positions[i] += particles.velocities[i] * dt;
+        }
+        neighbors.rebuild(&particles.positions, smoothing_radius);
+
+        let mut density_error = 0.0;
+        for _ in 0..self.iterations {
+            sph::compute_densities(
+                &particles.positions,
+                &particles.phases,
+                neighbors,
+                None,
+                params,
+                &mut particles.densities,
+            );
+            density_error =
+                sph::average_density_error(&particles.densities, &particles.phases, params);
+
+            for i in 0..num_particles {
+                let constraint = (particles.densities[i] / params.rest_density - 1.0).max(0.0);
+
+                let mut gradient_sum = Vector2::zeros();
+                let mut gradient_sq_sum = 0.0;
+                for (j, image) in neighbors.with_images(i) {
+                    let gradient = scale
+                        * kernels.pressure.gradient(
+                            particles.positions[i] - image.position(particles.positions[j]),
+                            smoothing_radius,
+                        );
+                    gradient_sum += gradient;
+                    gradient_sq_sum += gradient.norm_squared();
+                }
+
+                self.denominators[i] =
+                    gradient_sum.norm_squared() + gradient_sq_sum + self.relaxation;
+                self.lambdas[i] = -constraint / self.denominators[i];
+            }
+
+            for i in 0..num_particles {
+                let mut correction = Vector2::zeros();
+                for (j, image) in neighbors.with_images(i) {
+                    let r = particles.positions[i] - image.position(particles.positions[j]);
+                    let tensile = -self.tensile_strength
+                        * (kernels.density.value(r.norm(), smoothing_radius) / tensile_reference)
+                            .powi(self.tensile_exponent as i32)
+                        * 0.5
+                        * (1.0 / self.denominators[i] + 1.0 / self.denominators[j]);
+
+                    correction += (self.lambdas[i] + self.lambdas[j] + tensile)
+                        * kernels.pressure.gradient(r, smoothing_radius);
+                }
+                self.corrections[i] = correction * scale;
+            }
+
+            for (position, correction) in particles.positions.iter_mut().zip(&self.corrections) {
+                *position += correction;
+            }
+            sph::enforce_boundaries(
+                params,
+                &container,
+                None,
+                &mut particles.positions,
+                &mut particles.velocities,
+            );
+        }
+
+        for i in 0..num_particles {
+            particles.velocities[i] = (particles.positions[i] - self.previous_positions[i]) / dt;
+        }
+
+        sph::apply_xsph(
+            particles,
+            neighbors,
+            params,
+            self.xsph_viscosity,
+            &mut self.velocity_corrections,
+        );
+
+        for i in 0..num_particles {
+            particles.accelerations[i] =
+                (particles.velocities[i] - self.previous_velocities[i]) / dt;
+        }
+
+        SolverStats {
+            iterations: self.iterations,
+            density_error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_parameters_are_rejected() {
+        assert_eq!(Pbf::new(0).err(), Some(ParamsError::Zero("PBF iterations")));
+        assert!(matches!(
+            Pbf::default().with_relaxation(0.0),
+            Err(ParamsError::NotPositive("PBF relaxation", _))
+        ));
+        assert!(matches!(
+            Pbf::default().with_tensile_correction(0.1, 1.0, 4),
+            Err(ParamsError::OutOfRange {
+                name: "PBF tensile distance",
+                ..
+            })
+        ));
+        assert_eq!(
+            Pbf::default().with_tensile_correction(0.1, 0.2, 0).err(),
+            Some(ParamsError::Zero("PBF tensile exponent"))
+        );
+        assert!(matches!(
+            Pbf::default().with_xsph_viscosity(1.5),
+            Err(ParamsError::OutOfRange {
+                name: "PBF XSPH factor",
+                ..
+            })
+        ));
+        assert!(Pbf::new(2)
+            .and_then(|solver| solver.with_relaxation(100.0))
+            .and_then(|solver| solver.with_tensile_correction(0.0, 0.1, 4))
+            .and_then(|solver| solver.with_xsph_viscosity(0.0))
+            .is_ok());
+    }
+}
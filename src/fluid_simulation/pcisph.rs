@@ -0,0 +1,356 @@
+use nalgebra::Vector2;
+
+use super::{
+    params::{in_range, non_negative},
+    rheology, sph, Kernel, ParamsError, SimulationParams, SmoothingKernel, Solver, SolverContext,
+    SolverStats,
+};
+
+/// Predictive-corrective incompressible SPH (Solenthaler & Pajarola 2009).
+///
+/// Pressures are corrected from the density error of predicted positions
+/// until the mean relative compression drops below `tolerance`. Pressure
+/// forces use the gradient of the density kernel like DFSPH and IISPH, with a
+/// different pressure kernel the correction doesn't act on the density it
+/// measures and pressures can build up without bound.
+pub struct Pcisph {
+    tolerance: f32,
+    min_iterations: u32,
+    max_iterations: u32,
+
+    non_pressure_accelerations: Vec<Vector2<f32>>,
+    pressure_accelerations: Vec<Vector2<f32>>,
+    predicted_positions: Vec<Vector2<f32>>,
+    predicted_velocities: Vec<Vector2<f32>>,
+    predicted_densities: Vec<f32>,
+    scaling_factors: Vec<f32>,
+}
+
+impl Pcisph {
+    /// `min_iterations` must be between 1 and `max_iterations`.
+    pub fn new(
+        tolerance: f32,
+        min_iterations: u32,
+        max_iterations: u32,
+    ) -> Result<Self, ParamsError> {
+        non_negative("PCISPH tolerance", tolerance)?;
+        in_range(
+            "PCISPH min iterations",
+            min_iterations as f32,
+            1.0,
+            max_iterations as f32,
+        )?;
+
+        Ok(Self {
+            tolerance,
+            min_iterations,
+            max_iterations,
+            ..Self::default()
+        })
+    }
+
+    /// Mean relative density error the corrections stop at.
+    pub fn tolerance(&self) -> f32 {
+        self.tolerance
+    }
+
+    pub fn min_iterations(&self) -> u32 {
+        self.min_iterations
+    }
+
+    pub fn max_iterations(&self) -> u32 {
+        self.max_iterations
+    }
+
+    /// Pressure change per unit of density error, evaluated on a prototype
+    /// particle with a filled neighborhood at rest spacing. `volume` is the
+    /// rest volume of the particle's phase.
+    ///
+    /// `1 / (beta (|sum grad W|^2 + sum |grad W|^2))` with `beta = (dt V)^2`
+    /// for the symmetric force `m (p_i + p_j) / (2 rho_i rho_j) grad W` of
+    /// `sph::add_pressure_accelerations` under a uniform pressure.
+    fn scaling_factor(params: &SimulationParams, volume: f32, dt: f32) -> f32 {
+        let spacing = volume.sqrt();
+        let extent = (params.smoothing_radius / spacing).ceil() as i32;
+
+        let mut gradient_sum = Vector2::zeros();
+        let mut gradient_sq_sum = 0.0;
+        for x in -extent..=extent {
+            for y in -extent..=extent {
+                let r = Vector2::new(x as f32, y as f32) * spacing;
+                let gradient = params.kernels.density.gradient(r, params.smoothing_radius);
+
+                gradient_sum += gradient;
+                gradient_sq_sum += gradient.norm_squared();
+            }
+        }
+
+        let beta = (dt * volume).powi(2);
+        1.0 / (beta * (gradient_sum.norm_squared() + gradient_sq_sum))
+    }
+}
+
+impl Default for Pcisph {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.01,
+            min_iterations: 3,
+            max_iterations: 50,
+            non_pressure_accelerations: Vec::new(),
+            pressure_accelerations: Vec::new(),
+            predicted_positions: Vec::new(),
+            predicted_velocities: Vec::new(),
+            predicted_densities: Vec::new(),
+            scaling_factors: Vec::new(),
+        }
+    }
+}
+
+impl Solver for Pcisph {
+    fn name(&self) -> &'static str {
+        "PCISPH"
+    }
+
+    fn supports_boundary_particles(&self) -> bool {
+        true
+    }
+
+    fn supports_phases(&self) -> bool {
+        true
+    }
+
+    fn pressure_kernel(&self, params: &SimulationParams) -> SmoothingKernel {
+        params.kernels.density
+    }
+
+    fn step(&mut self, context: &mut SolverContext, dt: f32) -> SolverStats {
+        let params = context.params;
+        let particles = &mut *context.particles;
+        let neighbors = &mut *context.neighbors;
+        let boundary = context.boundary;
+        let container = context.container;
+        let num_particles = particles.len();
+        let pressure_kernel = self.pressure_kernel(params);
+
+        neighbors.rebuild(&particles.positions, params.smoothing_radius);
+        sph::compute_densities(
+            &particles.positions,
+            &particles.phases,
+            neighbors,
+            boundary,
+            params,
+            &mut particles.densities,
+        );
+
+        self.non_pressure_accelerations
+            .resize(num_particles, Vector2::zeros());
+        rheology::compute_viscosities(particles, neighbors, params);
+        sph::non_pressure_accelerations(
+            particles,
+            neighbors,
+            params,
+            &mut self.non_pressure_accelerations,
+        );
+
+        self.pressure_accelerations.clear();
+        self.pressure_accelerations
+            .resize(num_particles, Vector2::zeros());
+        self.predicted_positions
+            .resize(num_particles, Vector2::zeros());
+        self.predicted_velocities
+            .resize(num_particles, Vector2::zeros());
+        self.predicted_densities.resize(num_particles, 0.0);
+        particles.pressures.fill(0.0);
+
+        // Pressure corrections of heavy phases are scaled down to the stiffness
+        // of the lightest one, otherwise they overshoot on light neighbors at
+        // the interface
+        let min_rest_density = (0..params.num_phases() as u32)
+            .map(|id| params.phase(id).rest_density)
+            .fold(f32::INFINITY, f32::min);
+        self.scaling_factors.clear();
+        self.scaling_factors
+            .extend((0..params.num_phases() as u32).map(|id| {
+                let phase = params.phase(id);
+                Self::scaling_factor(params, phase.volume(), dt) * min_rest_density
+                    / phase.rest_density
+            }));
+
+        // The error is measured on the state the current pressure accelerations
+        // lead to, so the reported error is the one of the integrated step
+        let mut iterations = 0;
+        let density_error = loop {
+            for i in 0..num_particles {
+                self.predicted_velocities[i] = particles.velocities[i]
+                    + (self.non_pressure_accelerations[i] + self.pressure_accelerations[i]) * dt;
+                self.predicted_positions[i] =
+                    particles.positions[i] + self.predicted_velocities[i] * dt;
+            }
+            sph::enforce_boundaries(
+                params,
+                &container,
+                boundary,
+                &mut self.predicted_positions,
+                &mut self.predicted_velocities,
+            );
+
+            sph::compute_densities(
+                &self.predicted_positions,
+                &particles.phases,
+                neighbors,
+                boundary,
+                params,
+                &mut self.predicted_densities,
+            );
+
+            let density_error =
+                sph::average_density_error(&self.predicted_densities, &particles.phases, params);
+            if iterations >= self.max_iterations
+                || (iterations >= self.min_iterations && density_error < self.tolerance)
+            {
+                break density_error;
+            }
+
+            for i in 0..num_particles {
+                let phase = particles.phases[i];
+                let scaling_factor = self
+                    .scaling_factors
+                    .get(phase as usize)
+                    .copied()
+                    .unwrap_or(self.scaling_factors[0]);
+
+                let error = self.predicted_densities[i] - params.phase(phase).rest_density;
+                particles.pressures[i] = (particles.pressures[i] + scaling_factor * error).max(0.0);
+            }
+
+            self.pressure_accelerations.fill(Vector2::zeros());
+            sph::add_pressure_accelerations(
+                particles,
+                &self.predicted_positions,
+                &self.predicted_densities,
+                neighbors,
+                boundary,
+                params,
+                pressure_kernel,
+                &mut self.pressure_accelerations,
+            );
+
+            iterations += 1;
+        };
+
+        for i in 0..num_particles {
+            particles.accelerations[i] =
+                self.non_pressure_accelerations[i] + self.pressure_accelerations[i];
+            particles.velocities[i] += particles.accelerations[i] * dt;
+            particles.positions[i] += particles.velocities[i] * dt;
+        }
+
+        SolverStats {
+            iterations,
+            density_error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fluid_simulation::{BoundaryParticles, FluidSimulation, TimeStepping};
+
+    /// Column of fluid on a lattice at rest spacing, filling a narrow domain
+    /// from wall to wall so it has nowhere to flow.
+    fn resting_column(with_boundary: bool) -> FluidSimulation {
+        let mass = 2.5e-4;
+        let spacing = f32::sqrt(mass);
+        // Without boundary particles the walls clamp one smoothing radius inside
+        let margin = if with_boundary { spacing } else { 0.04 };
+        let (columns, rows) = (8, 12);
+        let width = columns as f32 * spacing + 2.0 * margin;
+
+        let params = SimulationParams::builder()
+            .mass(mass)
+            .domain_size(Vector2::new(width, 0.5))
+            .time_stepping(TimeStepping {
+                step: 0.004,
+                max_steps_per_update: u32::MAX,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        let mut positions = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                positions.push(
+                    Vector2::new(-0.5 * width, -0.25)
+                        + Vector2::repeat(margin)
+                        + Vector2::new(column as f32 + 0.5, row as f32 + 0.5) * spacing,
+                );
+            }
+        }
+        let velocities = vec![Vector2::zeros(); positions.len()];
+
+        let simulation = FluidSimulation::with_particles(params.clone(), positions, velocities)
+            .with_solver(Pcisph::default());
+        if with_boundary {
+            simulation.with_boundary_particles(BoundaryParticles::domain_walls(&params, spacing))
+        } else {
+            simulation
+        }
+    }
+
+    #[test]
+    fn invalid_parameters_are_rejected() {
+        assert!(matches!(
+            Pcisph::new(-0.01, 3, 50),
+            Err(ParamsError::Negative("PCISPH tolerance", _))
+        ));
+        assert!(matches!(
+            Pcisph::new(0.01, 0, 50),
+            Err(ParamsError::OutOfRange {
+                name: "PCISPH min iterations",
+                ..
+            })
+        ));
+        assert!(matches!(
+            Pcisph::new(0.01, 3, 2),
+            Err(ParamsError::OutOfRange {
+                name: "PCISPH min iterations",
+                ..
+            })
+        ));
+        assert!(Pcisph::new(0.01, 3, 3).is_ok());
+    }
+
+    #[test]
+    fn resting_column_stays_bounded() {
+        for with_boundary in [false, true] {
+            let mut simulation = resting_column(with_boundary);
+            let num_particles = simulation.particles().len() as f32;
+
+            for frame in 1..=8 {
+                simulation.update(0.05);
+
+                let particles = simulation.particles();
+                let max_speed = particles
+                    .velocities
+                    .iter()
+                    .map(|velocity| velocity.norm())
+                    .fold(0.0, f32::max);
+                let mean_density = particles.densities.iter().sum::<f32>() / num_particles;
+                let density_error = simulation.last_step().density_error;
+
+                let context = format!("boundary particles {with_boundary}, frame {frame}");
+                assert!(max_speed < 1.0, "{context}: max speed {max_speed}");
+                assert!(
+                    (0.85..1.05).contains(&mean_density),
+                    "{context}: mean density {mean_density}"
+                );
+                assert!(
+                    density_error < 0.01,
+                    "{context}: density error {density_error}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,23 @@
+/// Fluid a particle belongs to, e.g. oil or water.
+///
+/// Phase 0 is the base fluid described by the mass, rest density and
+/// viscosity of `SimulationParams`, further phases are numbered from 1 in the
+/// order they were added.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Phase {
+    pub rest_density: f32,
+    pub mass: f32,
+    pub viscosity: f32,
+    /// Linear RGB render color.
+    pub color: [f32; 3],
+}
+
+impl Phase {
+    /// Render color of the base fluid.
+    pub const BASE_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+    /// Rest volume of a particle, equal for all phases in a typical setup.
+    pub fn volume(&self) -> f32 {
+        self.mass / self.rest_density
+    }
+}
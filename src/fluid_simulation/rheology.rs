@@ -0,0 +1,135 @@
+use nalgebra::Matrix2;
+
+use super::{Kernel, NeighborList, Particles, SimulationParams};
+
+/// Dependence of the dynamic viscosity on the shear rate.
+///
+/// The shear rate is `sqrt(2 D : D)` of the strain rate tensor `D`, the
+/// symmetric part of the SPH velocity gradient of each particle.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ViscosityModel {
+    /// Constant viscosity of the particle's phase.
+    #[default]
+    Newtonian,
+    /// `K * shear_rate^(n - 1)`, shear thinning for `n < 1` (paint),
+    /// thickening for `n > 1`. Capped at `max_viscosity` since thinning
+    /// fluids are infinitely viscous at rest.
+    PowerLaw {
+        consistency: f32,
+        index: f32,
+        max_viscosity: f32,
+    },
+    /// `mu_inf + (mu_0 - mu_inf) (1 + (lambda shear_rate)^2)^((n - 1) / 2)`.
+    Carreau {
+        zero_shear_viscosity: f32,
+        infinite_shear_viscosity: f32,
+        relaxation_time: f32,
+        index: f32,
+    },
+    /// `mu_inf + (mu_0 - mu_inf) / (1 + (lambda shear_rate)^m)`.
+    Cross {
+        zero_shear_viscosity: f32,
+        infinite_shear_viscosity: f32,
+        time_constant: f32,
+        index: f32,
+    },
+    /// Flows like a fluid of `plastic_viscosity` only above `yield_stress`
+    /// (ketchup, mud), with the Papanastasiou regularization
+    /// `mu_p + tau_y (1 - exp(-m shear_rate)) / shear_rate`. Larger
+    /// `regularization` approaches the ideal Bingham plastic more closely, at
+    /// rest the viscosity is `mu_p + tau_y m`, which also bounds the adaptive
+    /// time step of the explicit viscosity.
+    Bingham {
+        yield_stress: f32,
+        plastic_viscosity: f32,
+        regularization: f32,
+    },
+}
+
+impl ViscosityModel {
+    /// Effective viscosity at `shear_rate`, `newtonian` is the viscosity of the particle's phase.
+    pub fn viscosity(&self, newtonian: f32, shear_rate: f32) -> f32 {
+        match *self {
+            Self::Newtonian => newtonian,
+            Self::PowerLaw {
+                consistency,
+                index,
+                max_viscosity,
+            } => (consistency * shear_rate.powf(index - 1.0)).min(max_viscosity),
+            Self::Carreau {
+                zero_shear_viscosity,
+                infinite_shear_viscosity,
+                relaxation_time,
+                index,
+            } => {
+                infinite_shear_viscosity
+                    + (zero_shear_viscosity - infinite_shear_viscosity)
+                        * (1.0 + (relaxation_time * shear_rate).powi(2)).powf(0.5 * (index - 1.0))
+            }
+            Self::Cross {
+                zero_shear_viscosity,
+                infinite_shear_viscosity,
+                time_constant,
+                index,
+            } => {
+                infinite_shear_viscosity
+                    + (zero_shear_viscosity - infinite_shear_viscosity)
+                        / (1.0 + (time_constant * shear_rate).powf(index))
+            }
+            Self::Bingham {
+                yield_stress,
+                plastic_viscosity,
+                regularization,
+            } => {
+                let exponent = regularization * shear_rate;
+                let plasticity = if exponent > 1e-4 {
+                    -(-exponent).exp_m1() / shear_rate
+                } else {
+                    regularization
+                };
+
+                plastic_viscosity + yield_stress * plasticity
+            }
+        }
+    }
+}
+
+/// Effective viscosity of every particle from its shear rate.
+pub(super) fn compute_viscosities(
+    particles: &mut Particles,
+    neighbors: &NeighborList,
+    params: &SimulationParams,
+) {
+    let Particles {
+        positions,
+        velocities,
+        densities,
+        phases,
+        viscosities,
+        ..
+    } = particles;
+    viscosities.resize(positions.len(), 0.0);
+
+    for (i, viscosity) in viscosities.iter_mut().enumerate() {
+        let newtonian = params.phase(phases[i]).viscosity;
+        if params.viscosity_model == ViscosityModel::Newtonian {
+            *viscosity = newtonian;
+            continue;
+        }
+
+        let mut velocity_gradient = Matrix2::zeros();
+        for (j, image) in neighbors.with_images(i) {
+            let gradient = params.kernels.density.gradient(
+                positions[i] - image.position(positions[j]),
+                params.smoothing_radius,
+            );
+            velocity_gradient += params.phase(phases[j]).mass / (densities[j] + 1e-6)
+                * (image.vector(velocities[j]) - velocities[i])
+                * gradient.transpose();
+        }
+
+        let strain_rate = 0.5 * (velocity_gradient + velocity_gradient.transpose());
+        let shear_rate = (2.0 * strain_rate.norm_squared()).sqrt();
+        *viscosity = params.viscosity_model.viscosity(newtonian, shear_rate);
+    }
+}
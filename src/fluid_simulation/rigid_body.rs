@@ -0,0 +1,670 @@
+use std::f32::consts::{PI, TAU};
+
+use nalgebra::{Isometry2, Vector2};
+
+use super::{
+    obstacles,
+    params::{in_range, non_negative, positive},
+    Kernel, KinematicState, ParamsError, Particles, Polygon, SignedDistance, SimulationParams,
+    SmoothingKernel,
+};
+
+/// Contact samples per polygon edge and around a disk.
+const EDGE_SAMPLES: usize = 8;
+const DISK_SAMPLES: usize = 32;
+
+/// Shape of a rigid body in its rest frame, centered on the center of mass.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RigidShape {
+    Disk { radius: f32 },
+    Box { half_extents: Vector2<f32> },
+    Polygon(Polygon),
+}
+
+impl RigidShape {
+    /// Area, centroid and polar second moment of area about the centroid.
+    fn mass_properties(&self) -> (f32, Vector2<f32>, f32) {
+        match self {
+            Self::Disk { radius } => {
+                let area = PI * radius * radius;
+                (area, Vector2::zeros(), 0.5 * area * radius * radius)
+            }
+            Self::Box { half_extents } => {
+                let area = 4.0 * half_extents.x * half_extents.y;
+                (
+                    area,
+                    Vector2::zeros(),
+                    area * half_extents.norm_squared() / 3.0,
+                )
+            }
+            Self::Polygon(polygon) => {
+                let mut area = 0.0;
+                let mut first_moment = Vector2::zeros();
+                let mut second_moment = 0.0;
+
+                let mut previous = polygon.vertices[polygon.vertices.len() - 1];
+                for &vertex in &polygon.vertices {
+                    let cross = previous.perp(&vertex);
+                    area += 0.5 * cross;
+                    first_moment += (previous + vertex) * cross / 6.0;
+                    second_moment += cross
+                        * (previous.norm_squared() + previous.dot(&vertex) + vertex.norm_squared())
+                        / 12.0;
+                    previous = vertex;
+                }
+
+                // Signs cancel for clockwise winding
+                let centroid = first_moment / area;
+                (
+                    area.abs(),
+                    centroid,
+                    (second_moment - area * centroid.norm_squared()).abs(),
+                )
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), ParamsError> {
+        match self {
+            Self::Disk { radius } => positive("disk radius", *radius),
+            Self::Box { half_extents } => {
+                positive("box half width", half_extents.x)?;
+                positive("box half height", half_extents.y)
+            }
+            // Vertices are checked by `Polygon::new`, a degenerate outline by the area
+            Self::Polygon(_) => Ok(()),
+        }
+    }
+
+    fn contact_points(&self) -> Vec<Vector2<f32>> {
+        self.outline_points(|_| DISK_SAMPLES, |_| EDGE_SAMPLES)
+    }
+
+    /// Points along the outline no further than `spacing` apart.
+    fn boundary_points(&self, spacing: f32) -> Vec<Vector2<f32>> {
+        let count = |length: f32| ((length / spacing).ceil() as usize).max(1);
+        self.outline_points(count, count)
+    }
+
+    /// Outline samples, with the number of points around a disk and along a
+    /// polygon edge given by the circumference and the edge length.
+    fn outline_points(
+        &self,
+        disk_samples: impl Fn(f32) -> usize,
+        edge_samples: impl Fn(f32) -> usize,
+    ) -> Vec<Vector2<f32>> {
+        match self {
+            Self::Disk { radius } => {
+                let count = disk_samples(TAU * radius).max(3);
+                (0..count)
+                    .map(|k| {
+                        let angle = TAU * k as f32 / count as f32;
+                        Vector2::new(angle.cos(), angle.sin()) * *radius
+                    })
+                    .collect()
+            }
+            Self::Box { half_extents } => {
+                Polygon::rectangle(Vector2::zeros(), *half_extents).outline_points(edge_samples)
+            }
+            Self::Polygon(polygon) => polygon.outline_points(edge_samples),
+        }
+    }
+}
+
+impl Polygon {
+    fn outline_points(&self, edge_samples: impl Fn(f32) -> usize) -> Vec<Vector2<f32>> {
+        let mut points = Vec::new();
+        let mut previous = self.vertices[self.vertices.len() - 1];
+        for &vertex in &self.vertices {
+            let count = edge_samples((vertex - previous).norm());
+            for k in 0..count {
+                points.push(previous + (vertex - previous) * (k as f32 / count as f32));
+            }
+            previous = vertex;
+        }
+        points
+    }
+}
+
+impl SignedDistance for RigidShape {
+    fn signed_distance(&self, point: Vector2<f32>) -> f32 {
+        match self {
+            Self::Disk { radius } => point.norm() - radius,
+            Self::Box { half_extents } => box_distance(point, *half_extents),
+            Self::Polygon(polygon) => polygon.signed_distance(point),
+        }
+    }
+
+    fn normal(&self, point: Vector2<f32>) -> Vector2<f32> {
+        match self {
+            Self::Disk { .. } => point.try_normalize(f32::EPSILON).unwrap_or_else(Vector2::y),
+            Self::Box { half_extents } => {
+                let offset = point.abs() - half_extents;
+                let normal = if offset.x > 0.0 || offset.y > 0.0 {
+                    offset.sup(&Vector2::zeros())
+                } else if offset.x > offset.y {
+                    Vector2::x()
+                } else {
+                    Vector2::y()
+                };
+                normal
+                    .component_mul(&point.map(|x| if x < 0.0 { -1.0 } else { 1.0 }))
+                    .normalize()
+            }
+            Self::Polygon(polygon) => polygon.normal(point),
+        }
+    }
+}
+
+/// Signed distance to an axis aligned box centered on the origin.
+pub(super) fn box_distance(point: Vector2<f32>, half_extents: Vector2<f32>) -> f32 {
+    let offset = point.abs() - half_extents;
+    offset.sup(&Vector2::zeros()).norm() + offset.x.max(offset.y).min(0.0)
+}
+
+/// Dynamic body that is pushed by the fluid and pushes back.
+///
+/// Fluid particles collide with the body like with an [`super::Obstacle`],
+/// and the momentum they exchange is applied back to the body. When the
+/// simulation uses [`super::BoundaryParticles`] and the solver supports them,
+/// the outline is also sampled with boundary particles at the fluid rest
+/// spacing (Akinci et al. 2012): the fluid sees the body in its densities and
+/// pressures and the body feels the reaction of the pressure forces, which
+/// gives buoyancy and drag. Without boundary particles the collision impulses
+/// are the only coupling, they stop the fluid from entering the body but
+/// don't carry the pressure of the surrounding fluid, so bodies don't float.
+///
+/// Buoyancy needs a smoothing radius of about twice the particle spacing.
+/// With fewer neighbors the fluid locks into a lattice that holds heavy
+/// bodies up, and solvers that take pressure forces from the density kernel,
+/// like PCISPH, need one with a gradient that keeps particles apart, such as
+/// the cubic spline rather than Poly6.
+///
+/// Bodies collide with the container walls, obstacles and each other through
+/// a set of points sampled on their outline.
+///
+/// Mass and inertia follow from `density`, which is in the same units as the
+/// fluid rest density, so a body lighter than the fluid floats.
+pub struct RigidBody {
+    pub(super) shape: RigidShape,
+    pub(super) position: Vector2<f32>,
+    pub(super) angle: f32,
+    pub(super) velocity: Vector2<f32>,
+    pub(super) angular_velocity: f32,
+    pub(super) restitution: f32,
+    pub(super) friction: f32,
+
+    mass: f32,
+    inertia: f32,
+    contact_points: Vec<Vector2<f32>>,
+    boundary_points: Vec<Vector2<f32>>,
+    boundary_volumes: Vec<f32>,
+}
+
+impl RigidBody {
+    /// Polygon vertices are shifted so that the body origin is the center of
+    /// mass, with `position` set to the centroid of the given vertices.
+    pub fn new(
+        mut shape: RigidShape,
+        density: f32,
+        restitution: f32,
+        friction: f32,
+    ) -> Result<Self, ParamsError> {
+        shape.validate()?;
+        positive("rigid body density", density)?;
+        in_range("restitution", restitution, 0.0, 1.0)?;
+        non_negative("friction", friction)?;
+
+        let (area, centroid, second_moment) = shape.mass_properties();
+        positive("rigid body area", area)?;
+        if let RigidShape::Polygon(polygon) = &mut shape {
+            for vertex in &mut polygon.vertices {
+                *vertex -= centroid;
+            }
+        }
+
+        Ok(Self {
+            contact_points: shape.contact_points(),
+            shape,
+            position: centroid,
+            angle: 0.0,
+            velocity: Vector2::zeros(),
+            angular_velocity: 0.0,
+            restitution,
+            friction,
+            mass: density * area,
+            inertia: density * second_moment,
+            boundary_points: Vec::new(),
+            boundary_volumes: Vec::new(),
+        })
+    }
+
+    pub fn with_position(mut self, position: Vector2<f32>) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_angle(mut self, angle: f32) -> Self {
+        self.angle = angle;
+        self
+    }
+
+    pub fn with_velocity(mut self, velocity: Vector2<f32>, angular_velocity: f32) -> Self {
+        self.velocity = velocity;
+        self.angular_velocity = angular_velocity;
+        self
+    }
+
+    pub fn shape(&self) -> &RigidShape {
+        &self.shape
+    }
+
+    pub fn position(&self) -> Vector2<f32> {
+        self.position
+    }
+
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+
+    pub fn velocity(&self) -> Vector2<f32> {
+        self.velocity
+    }
+
+    pub fn angular_velocity(&self) -> f32 {
+        self.angular_velocity
+    }
+
+    pub fn restitution(&self) -> f32 {
+        self.restitution
+    }
+
+    pub fn friction(&self) -> f32 {
+        self.friction
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.mass
+    }
+
+    /// Moment of inertia about the center of mass.
+    pub fn inertia(&self) -> f32 {
+        self.inertia
+    }
+
+    pub fn state(&self) -> KinematicState {
+        KinematicState {
+            transform: Isometry2::new(self.position, self.angle),
+            center: self.position,
+            velocity: self.velocity,
+            angular_velocity: self.angular_velocity,
+        }
+    }
+
+    pub fn kinetic_energy(&self) -> f32 {
+        0.5 * (self.mass * self.velocity.norm_squared()
+            + self.inertia * self.angular_velocity * self.angular_velocity)
+    }
+
+    /// Resamples the boundary particles for the rest spacing and kernel of
+    /// `params`, volumes are computed like for [`super::BoundaryParticles`].
+    pub(super) fn update_boundary(&mut self, params: &SimulationParams) {
+        let spacing = params.phase(0).volume().sqrt();
+        self.boundary_points = self.shape.boundary_points(spacing);
+
+        let points = &self.boundary_points;
+        self.boundary_volumes = points
+            .iter()
+            .map(|&point| {
+                let kernel_sum: f32 = points
+                    .iter()
+                    .map(|&other| {
+                        params
+                            .kernels
+                            .density
+                            .value((point - other).norm(), params.smoothing_radius)
+                    })
+                    .sum();
+                1.0 / kernel_sum
+            })
+            .collect();
+    }
+
+    /// World positions and volumes of the boundary particles.
+    pub(super) fn boundary_particles(&self) -> impl Iterator<Item = (Vector2<f32>, f32)> + '_ {
+        let state = self.state();
+        self.boundary_points
+            .iter()
+            .map(move |&point| state.to_world(point))
+            .zip(self.boundary_volumes.iter().copied())
+    }
+
+    /// Applies the reaction of the pressure forces the boundary particles
+    /// exert on the fluid, see `sph::add_pressure_accelerations`. `kernel`
+    /// must be the one the solver computed those forces with.
+    pub(super) fn apply_fluid_pressure(
+        &mut self,
+        params: &SimulationParams,
+        particles: &Particles,
+        kernel: SmoothingKernel,
+        dt: f32,
+    ) {
+        let state = self.state();
+        let mut force = Vector2::zeros();
+        let mut torque = 0.0;
+
+        for i in 0..particles.len() {
+            let pressure = particles.pressures[i].max(0.0);
+            let position = particles.positions[i];
+            if pressure <= 0.0
+                || self.shape.signed_distance(state.to_local(position)) >= params.smoothing_radius
+            {
+                continue;
+            }
+
+            let phase = params.phase(particles.phases[i]);
+            let density = particles.densities[i] + 1e-6;
+            let scale = phase.mass * phase.rest_density * pressure / (density * density);
+            for (point, volume) in self.boundary_particles() {
+                let particle_force =
+                    scale * volume * kernel.gradient(position - point, params.smoothing_radius);
+                force += particle_force;
+                torque += (point - self.position).perp(&particle_force);
+            }
+        }
+
+        self.velocity += force / self.mass * dt;
+        self.angular_velocity += torque / self.inertia * dt;
+    }
+
+    pub(super) fn integrate(&mut self, gravity: Vector2<f32>, dt: f32) {
+        self.velocity += gravity * dt;
+        self.position += self.velocity * dt;
+        self.angle += self.angular_velocity * dt;
+    }
+
+    /// Applies `impulse` at the world point `point`.
+    fn apply_impulse(&mut self, impulse: Vector2<f32>, point: Vector2<f32>) {
+        self.velocity += impulse / self.mass;
+        self.angular_velocity += (point - self.position).perp(&impulse) / self.inertia;
+    }
+
+    /// Inverse of the mass felt by an impulse along `direction` at `point`.
+    fn inverse_mass_at(&self, point: Vector2<f32>, direction: Vector2<f32>) -> f32 {
+        let lever = (point - self.position).perp(&direction);
+        1.0 / self.mass + lever * lever / self.inertia
+    }
+
+    /// Pushes particles out of the body and applies their momentum change back to it.
+    pub(super) fn collide_particles(
+        &mut self,
+        params: &SimulationParams,
+        margin: f32,
+        particles: &mut Particles,
+    ) {
+        let state = self.state();
+        let mut impulse = Vector2::zeros();
+        let mut angular_impulse = 0.0;
+
+        for ((position, velocity), &phase) in particles
+            .positions
+            .iter_mut()
+            .zip(particles.velocities.iter_mut())
+            .zip(&particles.phases)
+        {
+            let previous_velocity = *velocity;
+            obstacles::collide_with_surface(
+                &self.shape,
+                &state,
+                self.restitution,
+                self.friction,
+                margin,
+                position,
+                velocity,
+            );
+
+            let particle_impulse = params.phase(phase).mass * (previous_velocity - *velocity);
+            impulse += particle_impulse;
+            angular_impulse += (*position - self.position).perp(&particle_impulse);
+        }
+
+        self.velocity += impulse / self.mass;
+        self.angular_velocity += angular_impulse / self.inertia;
+    }
+
+    /// Resolves penetration of the body into a solid moving with `state`.
+    pub(super) fn collide_with_solid(
+        &mut self,
+        solid: &dyn SignedDistance,
+        state: &KinematicState,
+        restitution: f32,
+        friction: f32,
+    ) {
+        for k in 0..self.contact_points.len() {
+            let point = self.state().to_world(self.contact_points[k]);
+            let local = state.to_local(point);
+            let depth = -solid.signed_distance(local);
+            if depth <= 0.0 {
+                continue;
+            }
+
+            let normal = state.transform.rotation * solid.normal(local);
+            self.position += normal * depth;
+
+            let point = point + normal * depth;
+            let relative_velocity = self.state().velocity_at(point) - state.velocity_at(point);
+            let impulse = contact_impulse(
+                relative_velocity,
+                normal,
+                |direction| self.inverse_mass_at(point, direction),
+                restitution,
+                friction,
+            );
+            self.apply_impulse(impulse, point);
+        }
+    }
+
+    /// Resolves penetration between two bodies, each checked against the other's outline.
+    pub(super) fn collide_bodies(a: &mut Self, b: &mut Self) {
+        Self::collide_points(a, b);
+        Self::collide_points(b, a);
+    }
+
+    /// Contact points of `a` inside `b`.
+    fn collide_points(a: &mut Self, b: &mut Self) {
+        let restitution = (a.restitution * b.restitution).sqrt();
+        let friction = (a.friction * b.friction).sqrt();
+
+        for k in 0..a.contact_points.len() {
+            let point = a.state().to_world(a.contact_points[k]);
+            let b_state = b.state();
+            let local = b_state.to_local(point);
+            let depth = -b.shape.signed_distance(local);
+            if depth <= 0.0 {
+                continue;
+            }
+
+            // Separate in proportion to the inverse masses
+            let normal = b_state.transform.rotation * b.shape.normal(local);
+            let share = b.mass / (a.mass + b.mass);
+            a.position += normal * (depth * share);
+            b.position -= normal * (depth * (1.0 - share));
+
+            let relative_velocity = a.state().velocity_at(point) - b.state().velocity_at(point);
+            let impulse = contact_impulse(
+                relative_velocity,
+                normal,
+                |direction| {
+                    a.inverse_mass_at(point, direction) + b.inverse_mass_at(point, direction)
+                },
+                restitution,
+                friction,
+            );
+            a.apply_impulse(impulse, point);
+            b.apply_impulse(-impulse, point);
+        }
+    }
+}
+
+/// Impulse on the first body of a contact with Coulomb friction, zero if the
+/// bodies are already separating along `normal`.
+fn contact_impulse(
+    relative_velocity: Vector2<f32>,
+    normal: Vector2<f32>,
+    inverse_mass: impl Fn(Vector2<f32>) -> f32,
+    restitution: f32,
+    friction: f32,
+) -> Vector2<f32> {
+    let normal_velocity = relative_velocity.dot(&normal);
+    if normal_velocity >= 0.0 {
+        return Vector2::zeros();
+    }
+
+    let normal_impulse = -(1.0 + restitution) * normal_velocity / inverse_mass(normal);
+
+    let tangential = relative_velocity - normal * normal_velocity;
+    let Some(tangent) = tangential.try_normalize(f32::EPSILON) else {
+        return normal * normal_impulse;
+    };
+    let tangential_impulse =
+        (tangential.norm() / inverse_mass(tangent)).min(friction * normal_impulse);
+
+    normal * normal_impulse - tangent * tangential_impulse
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fluid_simulation::{
+        BoundaryParticles, FluidSimulation, Kernels, Pcisph, Solver, TimeStepping, Wcsph,
+    };
+
+    /// Disk of the given density released in the middle of a tank of resting
+    /// fluid bounded by boundary particles, at the resolution buoyancy needs,
+    /// see `RigidBody`.
+    fn submerged_disk(density: f32, solver: impl Solver + 'static) -> FluidSimulation {
+        let mass = 0.0015;
+        let spacing = f32::sqrt(mass);
+        let radius = 0.07;
+        let (width, height, rows) = (0.4, 0.7, 14);
+        let columns = ((width - 2.0 * spacing) / spacing) as usize;
+
+        let params = SimulationParams::builder()
+            .mass(mass)
+            .smoothing_radius(2.0 * spacing)
+            .kernels(Kernels {
+                density: SmoothingKernel::CubicSpline,
+                ..Kernels::default()
+            })
+            .domain_size(Vector2::new(width, height))
+            .time_stepping(TimeStepping {
+                step: 0.008,
+                max_steps_per_update: u32::MAX,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        let corner = -0.5 * Vector2::new(width, height) + Vector2::repeat(spacing);
+        let center = Vector2::new(0.0, corner.y + 0.5 * rows as f32 * spacing);
+        let mut positions = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                let position =
+                    corner + Vector2::new(column as f32 + 0.5, row as f32 + 0.5) * spacing;
+                if (position - center).norm() > radius + 0.8 * spacing {
+                    positions.push(position);
+                }
+            }
+        }
+        let velocities = vec![Vector2::zeros(); positions.len()];
+
+        let body = RigidBody::new(RigidShape::Disk { radius }, density, 0.0, 0.0)
+            .unwrap()
+            .with_position(center);
+        let mut simulation = FluidSimulation::with_particles(params.clone(), positions, velocities)
+            .with_boundary_particles(BoundaryParticles::domain_walls(&params, spacing))
+            .with_rigid_body(body);
+        simulation.set_solver(solver);
+        simulation
+    }
+
+    /// Top of the body at the start, and of the body and the fluid surface
+    /// after settling. The surface is the level the fluid's area fills across
+    /// the tank, sloshing and particles piled on the body don't move it.
+    fn settle(density: f32, solver: impl Solver + 'static) -> (f32, f32, f32) {
+        let mut simulation = submerged_disk(density, solver);
+        let start = simulation.rigid_bodies()[0].position();
+        for _ in 0..20 {
+            simulation.update(0.1);
+        }
+
+        let RigidShape::Disk { radius } = *simulation.rigid_bodies()[0].shape() else {
+            unreachable!()
+        };
+        let body = simulation.rigid_bodies()[0].position();
+        let params = simulation.params();
+        let fluid_area = simulation.num_particles() as f32 * params.mass / params.rest_density;
+        let fluid_top = -0.5 * params.domain_size.y + fluid_area / params.domain_size.x;
+        (start.y + radius, body.y + radius, fluid_top)
+    }
+
+    /// Settling takes a couple of seconds of simulated time, too slow for
+    /// debug builds, run with `cargo test --release -- --ignored`.
+    fn assert_buoyancy<S: Solver + 'static>(solver: impl Fn() -> S) {
+        let (_, body_top, fluid_top) = settle(0.5, solver());
+        assert!(
+            body_top > fluid_top + 0.05,
+            "light body top {body_top} below the fluid surface {fluid_top}"
+        );
+
+        let (start_top, body_top, fluid_top) = settle(3.0, solver());
+        assert!(
+            body_top < start_top - 0.07 && body_top < fluid_top - 0.07,
+            "heavy body top {body_top} started at {start_top}, fluid surface {fluid_top}"
+        );
+    }
+
+    #[test]
+    #[ignore = "slow in debug builds"]
+    fn light_body_floats_and_heavy_body_sinks_with_wcsph() {
+        assert_buoyancy(Wcsph::default);
+    }
+
+    #[test]
+    #[ignore = "slow in debug builds"]
+    fn light_body_floats_and_heavy_body_sinks_with_pcisph() {
+        assert_buoyancy(Pcisph::default);
+    }
+
+    #[test]
+    fn invalid_bodies_are_rejected() {
+        let disk = RigidShape::Disk { radius: 0.1 };
+        assert!(matches!(
+            RigidBody::new(RigidShape::Disk { radius: -0.1 }, 1.0, 0.5, 0.1),
+            Err(ParamsError::NotPositive("disk radius", _))
+        ));
+        assert!(matches!(
+            RigidBody::new(disk.clone(), 0.0, 0.5, 0.1),
+            Err(ParamsError::NotPositive("rigid body density", _))
+        ));
+        assert!(matches!(
+            RigidBody::new(disk.clone(), 1.0, 1.5, 0.1),
+            Err(ParamsError::OutOfRange {
+                name: "restitution",
+                ..
+            })
+        ));
+        assert!(matches!(
+            RigidBody::new(disk.clone(), 1.0, 0.5, -0.1),
+            Err(ParamsError::Negative("friction", _))
+        ));
+
+        let collinear = Polygon::new(vec![Vector2::zeros(), Vector2::x(), Vector2::new(2.0, 0.0)]);
+        assert!(matches!(
+            RigidBody::new(RigidShape::Polygon(collinear.unwrap()), 1.0, 0.5, 0.1),
+            Err(ParamsError::NotPositive("rigid body area", _))
+        ));
+        assert!(RigidBody::new(disk, 1.0, 0.5, 0.1).is_ok());
+    }
+}
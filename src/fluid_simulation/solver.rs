@@ -0,0 +1,153 @@
+use super::{
+    rheology, sph, BoundaryParticles, Integrator, KinematicState, NeighborList, Particles,
+    SimulationParams, SmoothingKernel, SymplecticEuler,
+};
+
+pub struct SolverContext<'a> {
+    pub params: &'a SimulationParams,
+    pub particles: &'a mut Particles,
+    pub neighbors: &'a mut NeighborList,
+    /// Only set for solvers that support boundary particles.
+    pub boundary: Option<&'a BoundaryParticles>,
+    /// Pose and velocity of the container at the end of the step.
+    pub container: KinematicState,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SolverStats {
+    /// Pressure solver iterations, zero for solvers without a pressure solve.
+    pub iterations: u32,
+    /// Mean relative compression `max(rho - rho_0, 0) / rho_0` of the final
+    /// state the solver evaluated.
+    pub density_error: f32,
+}
+
+/// Advances particle positions and velocities by one step.
+///
+/// Boundaries are enforced by the simulation after the step, solvers only
+/// need to handle them for intermediate (predicted) states.
+pub trait Solver {
+    fn name(&self) -> &'static str;
+
+    fn step(&mut self, context: &mut SolverContext, dt: f32) -> SolverStats;
+
+    /// Whether the stable step is limited by the speed of sound of the equation of state.
+    fn compressible(&self) -> bool {
+        false
+    }
+
+    /// Whether the solver accounts for `SolverContext::boundary`, other
+    /// solvers rely on the clamp at the domain walls.
+    fn supports_boundary_particles(&self) -> bool {
+        false
+    }
+
+    /// Kernel whose gradient the solver's pressure forces use. Rigid bodies
+    /// receive the reaction to the pressure forces of their boundary
+    /// particles with the same kernel, so momentum is conserved.
+    fn pressure_kernel(&self, params: &SimulationParams) -> SmoothingKernel {
+        params.kernels.pressure
+    }
+
+    /// Whether the solver handles particles of different phases, other
+    /// solvers simulate every particle as the base fluid.
+    fn supports_phases(&self) -> bool {
+        false
+    }
+
+    /// Called after particles were removed, `kept` has one entry per particle
+    /// before the removal. Only needed by solvers that keep per-particle state
+    /// between steps.
+    fn retain_particles(&mut self, _kept: &[bool]) {}
+}
+
+/// Weakly compressible SPH, pressure from the equation of state and explicit time integration.
+pub struct Wcsph {
+    integrator: Box<dyn Integrator>,
+}
+
+impl Wcsph {
+    pub fn new(integrator: impl Integrator + 'static) -> Self {
+        Self {
+            integrator: Box::new(integrator),
+        }
+    }
+
+    pub fn integrator(&self) -> &dyn Integrator {
+        self.integrator.as_ref()
+    }
+}
+
+impl Default for Wcsph {
+    fn default() -> Self {
+        Self::new(SymplecticEuler)
+    }
+}
+
+impl Solver for Wcsph {
+    fn name(&self) -> &'static str {
+        "WCSPH"
+    }
+
+    fn step(&mut self, context: &mut SolverContext, dt: f32) -> SolverStats {
+        let params = context.params;
+        let neighbors = &mut *context.neighbors;
+        let boundary = context.boundary;
+        let pressure_kernel = self.pressure_kernel(params);
+
+        self.integrator
+            .step(context.particles, dt, &mut |particles| {
+                neighbors.rebuild(&particles.positions, params.smoothing_radius);
+                sph::compute_densities(
+                    &particles.positions,
+                    &particles.phases,
+                    neighbors,
+                    boundary,
+                    params,
+                    &mut particles.densities,
+                );
+                sph::compute_pressures(
+                    &particles.densities,
+                    &particles.phases,
+                    params,
+                    &mut particles.pressures,
+                );
+
+                rheology::compute_viscosities(particles, neighbors, params);
+                let mut accelerations = std::mem::take(&mut particles.accelerations);
+                sph::non_pressure_accelerations(particles, neighbors, params, &mut accelerations);
+                sph::add_pressure_accelerations(
+                    particles,
+                    &particles.positions,
+                    &particles.densities,
+                    neighbors,
+                    boundary,
+                    params,
+                    pressure_kernel,
+                    &mut accelerations,
+                );
+                particles.accelerations = accelerations;
+            });
+
+        SolverStats {
+            iterations: 0,
+            density_error: sph::average_density_error(
+                &context.particles.densities,
+                &context.particles.phases,
+                params,
+            ),
+        }
+    }
+
+    fn compressible(&self) -> bool {
+        true
+    }
+
+    fn supports_boundary_particles(&self) -> bool {
+        true
+    }
+
+    fn supports_phases(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,253 @@
+use nalgebra::Vector2;
+
+use super::{
+    artificial_viscosity, heat, surface_tension, BoundaryMode, BoundaryParticles, Kernel,
+    KinematicState, NeighborList, Particles, SimulationParams, SmoothingKernel,
+};
+
+/// Fluid densities, boundary particles count as fluid at rest density (Akinci et al. 2012).
+///
+/// Densities are the particle's own mass times the number density
+/// `sum_j W_ij` (Solenthaler & Pajarola 2008), so a light phase next to a
+/// heavy one isn't smeared into a wrong density at the interface. With a
+/// single phase this is the usual `sum_j m W_ij`.
+pub(super) fn compute_densities(
+    positions: &[Vector2<f32>],
+    phases: &[u32],
+    neighbors: &NeighborList,
+    boundary: Option<&BoundaryParticles>,
+    params: &SimulationParams,
+    densities: &mut [f32],
+) {
+    let kernel = params.kernels.density;
+    let self_contribution = kernel.value(0.0, params.smoothing_radius);
+
+    for (i, density) in densities.iter_mut().enumerate() {
+        let phase = params.phase(phases[i]);
+        let mut number_density = self_contribution;
+
+        for (j, image) in neighbors.with_images(i) {
+            let r_norm = (positions[i] - image.position(positions[j])).norm();
+            number_density += kernel.value(r_norm, params.smoothing_radius);
+        }
+
+        if let Some(boundary) = boundary {
+            for b in boundary.neighbors(positions[i], params.smoothing_radius) {
+                let r_norm = (positions[i] - boundary.positions()[b]).norm();
+                number_density += boundary.volumes()[b] / phase.volume()
+                    * kernel.value(r_norm, params.smoothing_radius);
+            }
+        }
+
+        *density = phase.mass * number_density;
+    }
+}
+
+pub(super) fn compute_pressures(
+    densities: &[f32],
+    phases: &[u32],
+    params: &SimulationParams,
+    pressures: &mut [f32],
+) {
+    for ((pressure, &density), &phase) in pressures.iter_mut().zip(densities).zip(phases) {
+        *pressure = params.phase_pressure(density, params.phase(phase).rest_density);
+    }
+}
+
+/// Viscosity, surface tension and gravity including buoyancy, everything except the pressure gradient.
+///
+/// Uses the effective viscosities of `rheology::compute_viscosities`.
+pub(super) fn non_pressure_accelerations(
+    particles: &Particles,
+    neighbors: &NeighborList,
+    params: &SimulationParams,
+    accelerations: &mut [Vector2<f32>],
+) {
+    for (i, acceleration) in accelerations.iter_mut().enumerate() {
+        let mut force = Vector2::zeros();
+
+        for (j, image) in neighbors.with_images(i) {
+            let r_norm = (particles.positions[i] - image.position(particles.positions[j])).norm();
+            force += 0.5
+                * (particles.viscosities[i] + particles.viscosities[j])
+                * params.phase(particles.phases[j]).mass
+                * (image.vector(particles.velocities[j]) - particles.velocities[i])
+                / (particles.densities[j] + 1e-6)
+                * params
+                    .kernels
+                    .viscosity
+                    .laplacian(r_norm, params.smoothing_radius);
+        }
+
+        *acceleration = force / (particles.densities[i] + 1e-6)
+            + heat::buoyant_gravity(params, particles.temperatures[i]);
+    }
+
+    artificial_viscosity::add_artificial_viscosity_accelerations(
+        particles,
+        neighbors,
+        params,
+        accelerations,
+    );
+    surface_tension::add_surface_tension_accelerations(particles, neighbors, params, accelerations);
+}
+
+/// Pressure gradient, boundary particles mirror the pressure of the fluid
+/// particle, clamped to push only so walls don't stick.
+///
+/// Pressures and phases are taken from `particles`, positions and densities
+/// may be predicted ones. `kernel` is the one whose gradient the forces use.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn add_pressure_accelerations(
+    particles: &Particles,
+    positions: &[Vector2<f32>],
+    densities: &[f32],
+    neighbors: &NeighborList,
+    boundary: Option<&BoundaryParticles>,
+    params: &SimulationParams,
+    kernel: SmoothingKernel,
+    accelerations: &mut [Vector2<f32>],
+) {
+    let pressures = &particles.pressures;
+
+    for (i, acceleration) in accelerations.iter_mut().enumerate() {
+        let mut force = Vector2::zeros();
+
+        for (j, image) in neighbors.with_images(i) {
+            force -= params.phase(particles.phases[j]).mass * (pressures[i] + pressures[j])
+                / (2.0 * densities[j] + 1e-6)
+                * kernel.gradient(
+                    positions[i] - image.position(positions[j]),
+                    params.smoothing_radius,
+                );
+        }
+
+        if let Some(boundary) = boundary {
+            let pressure = pressures[i].max(0.0);
+            for b in boundary.neighbors(positions[i], params.smoothing_radius) {
+                force -= params.phase(particles.phases[i]).rest_density
+                    * boundary.volumes()[b]
+                    * pressure
+                    / (densities[i] + 1e-6)
+                    * kernel.gradient(
+                        positions[i] - boundary.positions()[b],
+                        params.smoothing_radius,
+                    );
+            }
+        }
+
+        *acceleration += force / (densities[i] + 1e-6);
+    }
+}
+
+/// XSPH velocity smoothing, `v_i += factor * sum_j m / rho_ij (v_j - v_i) W_ij`
+/// with the mean density `rho_ij`.
+pub(super) fn apply_xsph(
+    particles: &mut Particles,
+    neighbors: &NeighborList,
+    params: &SimulationParams,
+    factor: f32,
+    corrections: &mut Vec<Vector2<f32>>,
+) {
+    corrections.clear();
+    corrections.extend((0..particles.len()).map(|i| {
+        let mut correction = Vector2::zeros();
+        for (j, image) in neighbors.with_images(i) {
+            let r_norm = (particles.positions[i] - image.position(particles.positions[j])).norm();
+            correction += 2.0 * params.phase(particles.phases[j]).mass
+                / (particles.densities[i] + particles.densities[j])
+                * (image.vector(particles.velocities[j]) - particles.velocities[i])
+                * params
+                    .kernels
+                    .density
+                    .value(r_norm, params.smoothing_radius);
+        }
+        correction * factor
+    }));
+
+    for (velocity, correction) in particles.velocities.iter_mut().zip(corrections.iter()) {
+        *velocity += correction;
+    }
+}
+
+/// Mean relative compression `max(rho - rho_0, 0) / rho_0`.
+pub(super) fn average_density_error(
+    densities: &[f32],
+    phases: &[u32],
+    params: &SimulationParams,
+) -> f32 {
+    if densities.is_empty() {
+        return 0.0;
+    }
+
+    densities
+        .iter()
+        .zip(phases)
+        .map(|(density, &phase)| {
+            let rest_density = params.phase(phase).rest_density;
+            (density - rest_density).max(0.0) / rest_density
+        })
+        .sum::<f32>()
+        / densities.len() as f32
+}
+
+/// Clamps particles into the container and damps the velocity of clamped ones
+/// relative to the container walls.
+///
+/// Without boundary particles the clamp keeps one smoothing radius away from
+/// the walls, with them it only stops particles from leaving the container.
+/// Mirror axes reflect particles at the walls, periodic axes are left alone
+/// until `Domain::wrap_periodic` at the end of the step.
+pub(super) fn enforce_boundaries(
+    params: &SimulationParams,
+    container: &KinematicState,
+    boundary: Option<&BoundaryParticles>,
+    positions: &mut [Vector2<f32>],
+    velocities: &mut [Vector2<f32>],
+) {
+    let margin = if boundary.is_some() {
+        0.0
+    } else {
+        params.smoothing_radius
+    };
+    let half_extents = params.domain().half_extents;
+    let limits = [0, 1].map(|axis| match params.boundary_modes[axis] {
+        BoundaryMode::Wall => half_extents[axis] - margin,
+        BoundaryMode::Mirror => half_extents[axis],
+        BoundaryMode::Periodic => f32::INFINITY,
+    });
+
+    for (position, velocity) in positions.iter_mut().zip(velocities.iter_mut()) {
+        let mut local = container.to_local(*position);
+        if local.x.abs() <= limits[0] && local.y.abs() <= limits[1] {
+            continue;
+        }
+
+        let mut relative_velocity = container
+            .transform
+            .rotation
+            .inverse_transform_vector(&(*velocity - container.velocity_at(*position)));
+
+        for axis in 0..2 {
+            let limit = limits[axis];
+            if local[axis].abs() <= limit {
+                continue;
+            }
+
+            let wall = limit.copysign(local[axis]);
+            if params.boundary_modes[axis] == BoundaryMode::Mirror {
+                local[axis] = (2.0 * wall - local[axis]).clamp(-limit, limit);
+                if relative_velocity[axis] * wall > 0.0 {
+                    relative_velocity[axis] = -relative_velocity[axis];
+                }
+            } else {
+                relative_velocity[axis] *= params.bound_damping;
+                local[axis] = wall;
+            }
+        }
+
+        *position = container.to_world(local);
+        *velocity =
+            container.transform.rotation * relative_velocity + container.velocity_at(*position);
+    }
+}
@@ -0,0 +1,133 @@
+use std::f32::consts::PI;
+
+use nalgebra::Vector2;
+
+use super::{Kernel, NeighborList, Particles, SimulationParams};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SurfaceTension {
+    /// Cohesion and curvature minimization of Akinci et al. 2013.
+    ///
+    /// Also attracts particles inside the fluid, which counteracts the
+    /// particle deficiency at the free surface.
+    Akinci { coefficient: f32 },
+    /// Continuum surface force on the smoothed color field (Müller et al. 2003),
+    /// only applied to particles whose color field gradient exceeds `threshold`.
+    ColorField { coefficient: f32, threshold: f32 },
+}
+
+/// Akinci's cohesion spline, repulsive below `h / 2`, with the 3D coefficient scaled to 2D units.
+fn cohesion_spline(r: f32, smoothing_radius: f32) -> f32 {
+    if r >= smoothing_radius {
+        return 0.0;
+    }
+
+    let h = smoothing_radius;
+    let value = (h - r).powi(3) * r.powi(3);
+    let value = if 2.0 * r > h {
+        value
+    } else {
+        2.0 * value - h.powi(6) / 64.0
+    };
+
+    32.0 / (PI * h.powi(8)) * value
+}
+
+/// `sum_j m_j / rho_j grad W_ij`, the gradient of the smoothed color field.
+fn color_field_gradients(
+    particles: &Particles,
+    neighbors: &NeighborList,
+    params: &SimulationParams,
+) -> Vec<Vector2<f32>> {
+    (0..particles.len())
+        .map(|i| {
+            neighbors
+                .with_images(i)
+                .map(|(j, image)| {
+                    params.phase(particles.phases[j]).mass / particles.densities[j]
+                        * params.kernels.density.gradient(
+                            particles.positions[i] - image.position(particles.positions[j]),
+                            params.smoothing_radius,
+                        )
+                })
+                .sum()
+        })
+        .collect()
+}
+
+pub(super) fn add_surface_tension_accelerations(
+    particles: &Particles,
+    neighbors: &NeighborList,
+    params: &SimulationParams,
+    accelerations: &mut [Vector2<f32>],
+) {
+    let Some(surface_tension) = params.surface_tension else {
+        return;
+    };
+
+    let normals = color_field_gradients(particles, neighbors, params);
+
+    match surface_tension {
+        SurfaceTension::Akinci { coefficient } => {
+            for (i, acceleration) in accelerations.iter_mut().enumerate() {
+                let normal = normals[i] * params.smoothing_radius;
+
+                let mut force = Vector2::zeros();
+                for (j, image) in neighbors.with_images(i) {
+                    let r = particles.positions[i] - image.position(particles.positions[j]);
+                    let r_norm = r.norm();
+                    if r_norm <= 0.0 {
+                        continue;
+                    }
+
+                    let phase = params.phase(particles.phases[j]);
+                    let cohesion =
+                        phase.mass * cohesion_spline(r_norm, params.smoothing_radius) * r / r_norm;
+                    let curvature = normal - image.vector(normals[j]) * params.smoothing_radius;
+                    let correction = (params.phase(particles.phases[i]).rest_density
+                        + phase.rest_density)
+                        / (particles.densities[i] + particles.densities[j]);
+
+                    force -= correction * coefficient * (cohesion + curvature);
+                }
+
+                *acceleration += force;
+            }
+        }
+        SurfaceTension::ColorField {
+            coefficient,
+            threshold,
+        } => {
+            for (i, acceleration) in accelerations.iter_mut().enumerate() {
+                let normal_norm = normals[i].norm();
+                if normal_norm <= threshold {
+                    continue;
+                }
+
+                let self_laplacian = params.phase(particles.phases[i]).mass
+                    / particles.densities[i]
+                    * params
+                        .kernels
+                        .density
+                        .laplacian(0.0, params.smoothing_radius);
+                let laplacian = self_laplacian
+                    + neighbors
+                        .with_images(i)
+                        .map(|(j, image)| {
+                            let r_norm = (particles.positions[i]
+                                - image.position(particles.positions[j]))
+                            .norm();
+                            params.phase(particles.phases[j]).mass / particles.densities[j]
+                                * params
+                                    .kernels
+                                    .density
+                                    .laplacian(r_norm, params.smoothing_radius)
+                        })
+                        .sum::<f32>();
+
+                let curvature = -laplacian / normal_norm;
+                *acceleration += coefficient * curvature * normals[i] / particles.densities[i];
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+/// Timestep configuration used by `FluidSimulation::update`.
+///
+/// Frame time scaled by `time_scale` is accumulated and consumed in steps of
+/// `step` seconds, each split into `substeps` solver steps. At most
+/// `max_steps_per_update` steps are taken per update, any time beyond that is
+/// dropped so a slow frame can't snowball into ever longer updates.
+///
+/// With `adaptive` set the solver step is chosen from the stability conditions
+/// instead, `step / substeps` then only bounds it from above.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeStepping {
+    pub step: f32,
+    pub substeps: u32,
+    pub max_steps_per_update: u32,
+    pub time_scale: f32,
+    pub adaptive: Option<AdaptiveTimestep>,
+}
+
+impl TimeStepping {
+    pub fn substep(&self) -> f32 {
+        self.step / self.substeps as f32
+    }
+
+    pub fn max_substeps_per_update(&self) -> u32 {
+        self.max_steps_per_update.saturating_mul(self.substeps)
+    }
+}
+
+impl Default for TimeStepping {
+    fn default() -> Self {
+        Self {
+            step: 0.0001,
+            substeps: 1,
+            max_steps_per_update: 100,
+            time_scale: 1.0,
+            adaptive: None,
+        }
+    }
+}
+
+/// Safety factors for the CFL, force and viscous step conditions:
+///
+/// `dt <= cfl_factor * h / (c + v_max)`,
+/// `dt <= force_factor * sqrt(h / a_max)`,
+/// `dt <= viscosity_factor * h^2 / viscosity`.
+///
+/// With artificial viscosity enabled `1.2 * (alpha * c + beta * v_max)` is
+/// added to the signal speed of the CFL condition.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveTimestep {
+    pub cfl_factor: f32,
+    pub force_factor: f32,
+    pub viscosity_factor: f32,
+    pub min_step: f32,
+}
+
+impl Default for AdaptiveTimestep {
+    fn default() -> Self {
+        Self {
+            cfl_factor: 0.4,
+            force_factor: 0.25,
+            viscosity_factor: 0.125,
+            min_step: 1e-6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Accumulator {
+    pending: f32,
+}
+
+impl Accumulator {
+    pub fn add(&mut self, frame_time: f32, time_stepping: &TimeStepping) {
+        self.pending += frame_time.max(0.0) * time_stepping.time_scale;
+    }
+
+    pub fn pending(&self) -> f32 {
+        self.pending
+    }
+
+    pub fn consume(&mut self, dt: f32) {
+        self.pending = (self.pending - dt).max(0.0);
+    }
+
+    /// Takes as many whole fixed steps as fit in the accumulated time and the update budget.
+    pub fn take_fixed_steps(&mut self, time_stepping: &TimeStepping) -> u32 {
+        let steps = (self.pending / time_stepping.step).floor();
+        if steps > time_stepping.max_steps_per_update as f32 {
+            self.pending %= time_stepping.step;
+            return time_stepping.max_steps_per_update;
+        }
+
+        self.consume(steps * time_stepping.step);
+        steps as u32
+    }
+
+    pub fn reset(&mut self) {
+        self.pending = 0.0;
+    }
+}
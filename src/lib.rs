@@ -1,4 +1,5 @@
 pub mod application;
 mod application_state;
-mod fluid_simulation;
-mod pipelines;
\ No newline at end of file
+pub mod fluid_simulation;
+mod pipelines;
+mod view;
\ No newline at end of file
@@ -1,12 +1,24 @@
 struct VertexInput {
     @location(0) particle_pos: vec2<f32>,
+    @location(1) color: vec3<f32>,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) center: vec2<f32>,
+    @location(1) color: vec3<f32>,
 };
 
+// World to clip space, clip = world * scale + offset
+struct View {
+    scale: vec2<f32>,
+    offset: vec2<f32>,
+    half_extents: vec2<f32>,
+};
+
+@group(0) @binding(0) var<uniform> view: View;
+
+// Particle radius in world units
 const SIZE: f32 = 0.01;
 
 @vertex
@@ -25,8 +37,9 @@ fn vs_main(
 
     let pos = quad_vertices[in_vertex_index] + vertex_input.particle_pos;
 
-    out.clip_position = vec4<f32>(pos, 0.0, 1.0);
+    out.clip_position = vec4<f32>(pos * view.scale + view.offset, 0.0, 1.0);
     out.center = quad_vertices[in_vertex_index];
+    out.color = vertex_input.color;
     return out;
 }
 
@@ -34,7 +47,7 @@ fn vs_main(
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     if length(in.center) < SIZE {
-        return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+        return vec4<f32>(in.color, 1.0);
     } else {
         discard;
     }
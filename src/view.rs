@@ -0,0 +1,46 @@
+use crate::fluid_simulation::Domain;
+
+/// World to clip space transform `clip = world * scale + offset`, with the
+/// half extents of the domain the field texture covers.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ViewUniform {
+    scale: [f32; 2],
+    offset: [f32; 2],
+    half_extents: [f32; 2],
+    _padding: [f32; 2],
+}
+
+// Only `f32` fields under `repr(C)`, so there is no padding and every bit pattern is valid
+const _: () = assert!(std::mem::size_of::<ViewUniform>() == 8 * std::mem::size_of::<f32>());
+unsafe impl bytemuck::Zeroable for ViewUniform {}
+unsafe impl bytemuck::Pod for ViewUniform {}
+
+impl ViewUniform {
+    /// Fits the domain into the window, keeping its aspect ratio.
+    pub fn fit(domain: &Domain, width: u32, height: u32) -> Self {
+        let pixels_per_unit = (width as f32 / domain.size().x).min(height as f32 / domain.size().y);
+
+        Self {
+            scale: [
+                2.0 * pixels_per_unit / width as f32,
+                2.0 * pixels_per_unit / height as f32,
+            ],
+            offset: [0.0, 0.0],
+            half_extents: domain.half_extents.into(),
+            _padding: [0.0, 0.0],
+        }
+    }
+
+    /// Size in pixels of the domain on screen.
+    pub fn domain_pixels(&self, width: u32, height: u32) -> (u32, u32) {
+        let pixels = |half_extent: f32, scale: f32, size: u32| {
+            ((half_extent * scale * size as f32).round() as u32).max(1)
+        };
+
+        (
+            pixels(self.half_extents[0], self.scale[0], width),
+            pixels(self.half_extents[1], self.scale[1], height),
+        )
+    }
+}